@@ -0,0 +1,82 @@
+//! Runs every script in `benches/lox/` through `jlox_rs::run`, in both
+//! execution modes, and `jlox_rs::run_vm` and reports the best wall-clock time of a few runs.
+//! `cargo bench -- <name>` runs only the scripts whose file name contains
+//! `<name>`.
+
+use std::time::{Duration, Instant};
+
+use jlox_rs::{
+    error::LoxError,
+    interpreter::{ExecutionMode, Interpreter},
+    vm::Vm,
+};
+
+const RUNS: u32 = 5;
+
+fn best_of<F>(mut run: F) -> Duration
+where
+    F: FnMut() -> Result<(), LoxError>,
+{
+    let mut best = Duration::MAX;
+
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        run().expect("benchmark script failed");
+        best = best.min(start.elapsed());
+    }
+
+    best
+}
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+
+    let mut scripts: Vec<_> =
+        std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/benches/lox"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+            .collect();
+    scripts.sort();
+
+    let mut results = vec![];
+
+    for script in scripts {
+        let name = script.file_stem().unwrap().to_string_lossy().into_owned();
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !name.contains(filter.as_str()))
+        {
+            continue;
+        }
+
+        let source = std::fs::read_to_string(&script).unwrap();
+
+        let tree_walker = best_of(|| jlox_rs::run(&source, &mut Interpreter::new()));
+        let closure = best_of(|| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_execution_mode(ExecutionMode::Closure);
+            jlox_rs::run(&source, &mut interpreter)
+        });
+        let vm = best_of(|| jlox_rs::run_vm(&source, &mut Vm::new()));
+
+        results.push((name, tree_walker, closure, vm));
+    }
+
+    // Scripts print their results; keep the table together after all of it.
+    println!();
+    println!(
+        "{:<20}{:>16}{:>16}{:>16}",
+        "benchmark", "tree-walker", "closure", "vm"
+    );
+    for (name, tree_walker, closure, vm) in results {
+        println!(
+            "{:<20}{:>13.2} ms{:>13.2} ms{:>13.2} ms",
+            name,
+            tree_walker.as_secs_f64() * 1000.0,
+            closure.as_secs_f64() * 1000.0,
+            vm.as_secs_f64() * 1000.0
+        );
+    }
+    println!("(best of {} runs each)", RUNS);
+}
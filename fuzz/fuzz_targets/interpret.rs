@@ -0,0 +1,249 @@
+//! Runs generated programs through the resolver and interpreter. Programs
+//! follow the grammar, so most get past the parser, and use a handful of
+//! names, so most references resolve to something.
+
+#![no_main]
+
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+use arbitrary::Arbitrary;
+use jlox_rs::{error, interpreter::Interpreter, limits};
+use libfuzzer_sys::fuzz_target;
+
+/// Enough for loops to do some work without making each run slow.
+const STEPS: u64 = 10_000;
+
+/// Well below `limits::DEFAULT_CALL_DEPTH`, which `limits::STACK_SIZE` is
+/// sized for without instrumentation; ASan makes every frame several times
+/// larger.
+const CALL_DEPTH: usize = 256;
+
+#[derive(Debug, Arbitrary)]
+struct Program(Vec<Stmt>);
+
+#[derive(Debug, Arbitrary)]
+enum Stmt {
+    Expression(Expr),
+    Print(Expr),
+    Var(Name, Option<Expr>),
+    Block(Vec<Stmt>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    For(Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    Fun(Function),
+    Return(Option<Expr>),
+    Class(Name, Option<Name>, Vec<Function>),
+}
+
+#[derive(Debug, Arbitrary)]
+struct Function {
+    name: Name,
+    params: Vec<Name>,
+    body: Vec<Stmt>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Expr {
+    Number(u8),
+    String(Name),
+    True,
+    False,
+    Nil,
+    Unary(UnaryOp, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Logical(Box<Expr>, bool, Box<Expr>),
+    Grouping(Box<Expr>),
+    Variable(Name),
+    Assign(Name, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Get(Box<Expr>, Name),
+    Set(Box<Expr>, Name, Box<Expr>),
+    This,
+    Super(Name),
+}
+
+#[derive(Debug, Arbitrary)]
+enum UnaryOp {
+    Bang,
+    Minus,
+}
+
+#[derive(Debug, Arbitrary)]
+enum BinaryOp {
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Name {
+    A,
+    B,
+    F,
+    Init,
+    Class,
+    Clock,
+    Exit,
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Name::A => "a",
+            Name::B => "b",
+            Name::F => "f",
+            Name::Init => "init",
+            Name::Class => "C",
+            Name::Clock => "clock",
+            Name::Exit => "exit",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Writes `items` separated by `separator`.
+fn join<T: Display>(f: &mut Formatter<'_>, items: &[T], separator: &str) -> fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            f.write_str(separator)?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        join(f, &self.0, "\n")
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        join(f, &self.params, ", ")?;
+        f.write_str(") {\n")?;
+        join(f, &self.body, "\n")?;
+        f.write_str("\n}")
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::Expression(expr) => write!(f, "{};", expr),
+            Stmt::Print(expr) => write!(f, "print {};", expr),
+            Stmt::Var(name, None) => write!(f, "var {};", name),
+            Stmt::Var(name, Some(initializer)) => write!(f, "var {} = {};", name, initializer),
+            Stmt::Block(statements) => {
+                f.write_str("{\n")?;
+                join(f, statements, "\n")?;
+                f.write_str("\n}")
+            }
+            Stmt::If(condition, then_branch, None) => {
+                write!(f, "if ({}) {}", condition, then_branch)
+            }
+            Stmt::If(condition, then_branch, Some(else_branch)) => {
+                write!(f, "if ({}) {} else {}", condition, then_branch, else_branch)
+            }
+            Stmt::While(condition, body) => write!(f, "while ({}) {}", condition, body),
+            Stmt::For(initializer, condition, increment, body) => {
+                f.write_str("for (")?;
+                match initializer {
+                    Some(initializer) => write!(f, "{}", initializer)?,
+                    None => f.write_str(";")?,
+                }
+                if let Some(condition) = condition {
+                    write!(f, " {}", condition)?;
+                }
+                f.write_str(";")?;
+                if let Some(increment) = increment {
+                    write!(f, " {}", increment)?;
+                }
+                write!(f, ") {}", body)
+            }
+            Stmt::Fun(function) => write!(f, "fun {}", function),
+            Stmt::Return(None) => f.write_str("return;"),
+            Stmt::Return(Some(value)) => write!(f, "return {};", value),
+            Stmt::Class(name, superclass, methods) => {
+                write!(f, "class {}", name)?;
+                if let Some(superclass) = superclass {
+                    write!(f, " < {}", superclass)?;
+                }
+                f.write_str(" {\n")?;
+                join(f, methods, "\n")?;
+                f.write_str("\n}")
+            }
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(value) => write!(f, "{}", value),
+            Expr::String(name) => write!(f, "\"{}\"", name),
+            Expr::True => f.write_str("true"),
+            Expr::False => f.write_str("false"),
+            Expr::Nil => f.write_str("nil"),
+            Expr::Unary(UnaryOp::Bang, right) => write!(f, "!{}", right),
+            Expr::Unary(UnaryOp::Minus, right) => write!(f, "-{}", right),
+            Expr::Binary(left, operator, right) => {
+                let operator = match operator {
+                    BinaryOp::Plus => "+",
+                    BinaryOp::Minus => "-",
+                    BinaryOp::Star => "*",
+                    BinaryOp::Slash => "/",
+                    BinaryOp::EqualEqual => "==",
+                    BinaryOp::BangEqual => "!=",
+                    BinaryOp::Less => "<",
+                    BinaryOp::LessEqual => "<=",
+                    BinaryOp::Greater => ">",
+                    BinaryOp::GreaterEqual => ">=",
+                };
+                write!(f, "({} {} {})", left, operator, right)
+            }
+            Expr::Logical(left, and, right) => {
+                let operator = if *and { "and" } else { "or" };
+                write!(f, "({} {} {})", left, operator, right)
+            }
+            Expr::Grouping(expr) => write!(f, "({})", expr),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::Assign(name, value) => write!(f, "{} = {}", name, value),
+            Expr::Call(callee, arguments) => {
+                write!(f, "{}(", callee)?;
+                join(f, arguments, ", ")?;
+                f.write_str(")")
+            }
+            Expr::Get(object, name) => write!(f, "{}.{}", object, name),
+            Expr::Set(object, name, value) => write!(f, "{}.{} = {}", object, name, value),
+            Expr::This => f.write_str("this"),
+            Expr::Super(method) => write!(f, "super.{}", method),
+        }
+    }
+}
+
+fn run(source: &str) {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_step_limit(STEPS);
+    interpreter.set_call_depth_limit(CALL_DEPTH);
+    interpreter.set_output(io::sink());
+    let _ = error::capture(|| jlox_rs::run(source, &mut interpreter));
+}
+
+// Runs on a thread sized the way `jlox` sizes its own, so runaway recursion
+// has to be stopped by the call depth limit rather than the stack.
+fuzz_target!(|program: Program| {
+    let source = program.to_string();
+    limits::with_stack(move || run(&source));
+});
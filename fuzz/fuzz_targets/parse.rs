@@ -0,0 +1,130 @@
+//! Feeds arbitrary token streams to the parser, and what parses to the
+//! resolver. Tokens are generated as source text so that every stream scans.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use jlox_rs::{
+    error, interpreter::Interpreter, parser::Parser, resolver::Resolver, scanner::Scanner,
+};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Arbitrary)]
+enum Token {
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Dot,
+    Minus,
+    Plus,
+    Semicolon,
+    Slash,
+    Star,
+    Bang,
+    BangEqual,
+    Equal,
+    EqualEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Identifier(Name),
+    String(Name),
+    Number(u16, Option<u8>),
+    And,
+    Class,
+    Else,
+    False,
+    Fun,
+    For,
+    If,
+    Nil,
+    Or,
+    Print,
+    Return,
+    Super,
+    This,
+    True,
+    Var,
+    While,
+}
+
+/// A few names, so that declarations and references meet.
+#[derive(Debug, Arbitrary)]
+enum Name {
+    A,
+    B,
+    Init,
+}
+
+impl Name {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Name::A => "a",
+            Name::B => "b",
+            Name::Init => "init",
+        }
+    }
+}
+
+impl Token {
+    fn lexeme(&self) -> String {
+        let lexeme = match self {
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::LeftBrace => "{",
+            Token::RightBrace => "}",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::Minus => "-",
+            Token::Plus => "+",
+            Token::Semicolon => ";",
+            Token::Slash => "/",
+            Token::Star => "*",
+            Token::Bang => "!",
+            Token::BangEqual => "!=",
+            Token::Equal => "=",
+            Token::EqualEqual => "==",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Identifier(name) => name.as_str(),
+            Token::String(name) => return format!("\"{}\"", name.as_str()),
+            Token::Number(whole, None) => return whole.to_string(),
+            Token::Number(whole, Some(fraction)) => return format!("{}.{}", whole, fraction),
+            Token::And => "and",
+            Token::Class => "class",
+            Token::Else => "else",
+            Token::False => "false",
+            Token::Fun => "fun",
+            Token::For => "for",
+            Token::If => "if",
+            Token::Nil => "nil",
+            Token::Or => "or",
+            Token::Print => "print",
+            Token::Return => "return",
+            Token::Super => "super",
+            Token::This => "this",
+            Token::True => "true",
+            Token::Var => "var",
+            Token::While => "while",
+        };
+        lexeme.to_string()
+    }
+}
+
+fuzz_target!(|tokens: Vec<Token>| {
+    let source: Vec<String> = tokens.iter().map(Token::lexeme).collect();
+    let source = source.join(" ");
+
+    let _ = error::capture(|| {
+        let tokens = Scanner::new(&source)
+            .scan_tokens()
+            .expect("generated tokens always scan");
+        let statements = Parser::new(tokens).parse()?;
+        Resolver::new(&mut Interpreter::new()).resolve(&statements)
+    });
+});
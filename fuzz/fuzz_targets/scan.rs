@@ -0,0 +1,14 @@
+//! Feeds arbitrary text to the scanner, which must report bad input as
+//! errors rather than panic.
+
+#![no_main]
+
+use jlox_rs::{error, scanner::Scanner};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = error::capture(|| Scanner::new(source).scan_tokens());
+});
@@ -0,0 +1,217 @@
+use std::rc::Rc;
+
+use crate::{expr::*, object::Object, stmt::*};
+
+/// Draws a syntax tree as a Graphviz `digraph`. Each node is labelled with
+/// its kind and, where it has one, its operator or name; edges to children
+/// with a fixed role are labelled with that role.
+#[derive(Debug, Default)]
+pub struct AstDot {
+    lines: Vec<String>,
+    next_id: usize,
+}
+
+impl AstDot {
+    pub fn print(mut self, statements: &[Stmt]) -> String {
+        let program = self.node("Program");
+        for stmt in statements {
+            let child = stmt.accept(&mut self);
+            self.edge(program, child, "");
+        }
+
+        let mut builder = "digraph ast {\n  node [shape=box];\n".to_string();
+        for line in &self.lines {
+            builder.push_str("  ");
+            builder.push_str(line);
+            builder.push('\n');
+        }
+        builder.push_str("}\n");
+
+        builder
+    }
+
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.lines
+            .push(format!("n{} [label=\"{}\"];", id, escape(label)));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        if label.is_empty() {
+            self.lines.push(format!("n{} -> n{};", from, to));
+        } else {
+            self.lines
+                .push(format!("n{} -> n{} [label=\"{}\"];", from, to, label));
+        }
+    }
+
+    fn expr(&mut self, parent: usize, expr: &Expr, label: &str) {
+        let child = expr.accept(self);
+        self.edge(parent, child, label);
+    }
+
+    fn stmt(&mut self, parent: usize, stmt: &Stmt, label: &str) {
+        let child = stmt.accept(self);
+        self.edge(parent, child, label);
+    }
+
+    fn stmts(&mut self, parent: usize, statements: &[Stmt]) {
+        for stmt in statements {
+            self.stmt(parent, stmt, "");
+        }
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl ExprVisitor<usize> for AstDot {
+    fn visit_literal_expr(&mut self, expr: &ExprLiteral) -> usize {
+        match expr.value {
+            Object::Str(ref s) => self.node(&format!("\"{}\"", s)),
+            ref value => self.node(&value.to_string()),
+        }
+    }
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) -> usize {
+        let id = self.node(&format!("Unary {}", expr.operator.lexeme));
+        self.expr(id, &expr.right, "");
+        id
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) -> usize {
+        let id = self.node(&format!("Binary {}", expr.operator.lexeme));
+        self.expr(id, &expr.left, "left");
+        self.expr(id, &expr.right, "right");
+        id
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) -> usize {
+        let id = self.node("Grouping");
+        self.expr(id, &expr.expression, "");
+        id
+    }
+
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) -> usize {
+        self.node(&format!("Variable {}", expr.name.lexeme))
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) -> usize {
+        let id = self.node(&format!("Assign {}", expr.name.lexeme));
+        self.expr(id, &expr.value, "");
+        id
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) -> usize {
+        let id = self.node(&format!("Logical {}", expr.operator.lexeme));
+        self.expr(id, &expr.left, "left");
+        self.expr(id, &expr.right, "right");
+        id
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) -> usize {
+        let id = self.node("Call");
+        self.expr(id, &expr.callee, "callee");
+        for argument in &expr.arguments {
+            self.expr(id, argument, "");
+        }
+        id
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) -> usize {
+        let id = self.node(&format!("Get {}", expr.name.lexeme));
+        self.expr(id, &expr.object, "");
+        id
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) -> usize {
+        let id = self.node(&format!("Set {}", expr.name.lexeme));
+        self.expr(id, &expr.object, "object");
+        self.expr(id, &expr.value, "value");
+        id
+    }
+
+    fn visit_this_expr(&mut self, _expr: &ExprThis) -> usize {
+        self.node("This")
+    }
+
+    fn visit_super_expr(&mut self, expr: &ExprSuper) -> usize {
+        self.node(&format!("Super {}", expr.method.lexeme))
+    }
+}
+
+impl StmtVisitor<usize> for AstDot {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) -> usize {
+        let id = self.node("Expression");
+        self.expr(id, &stmt.expression, "");
+        id
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) -> usize {
+        let id = self.node("Print");
+        self.expr(id, &stmt.expression, "");
+        id
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) -> usize {
+        let id = self.node(&format!("Var {}", stmt.name.lexeme));
+        if let Some(ref initializer) = stmt.initializer {
+            self.expr(id, initializer, "");
+        }
+        id
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) -> usize {
+        let id = self.node("Block");
+        self.stmts(id, &stmt.statements);
+        id
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) -> usize {
+        let id = self.node("If");
+        self.expr(id, &stmt.condition, "condition");
+        self.stmt(id, &stmt.then_branch, "then");
+        if let Some(ref else_branch) = stmt.else_branch {
+            self.stmt(id, else_branch, "else");
+        }
+        id
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) -> usize {
+        let id = self.node("While");
+        self.expr(id, &stmt.condition, "condition");
+        self.stmt(id, &stmt.body, "body");
+        id
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) -> usize {
+        let params: Vec<&str> = stmt.params.iter().map(|p| p.lexeme.as_str()).collect();
+        let id = self.node(&format!(
+            "Function {}({})",
+            stmt.name.lexeme,
+            params.join(", ")
+        ));
+        self.stmts(id, &stmt.body);
+        id
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) -> usize {
+        let id = self.node("Return");
+        if let Some(ref value) = stmt.value {
+            self.expr(id, value, "");
+        }
+        id
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) -> usize {
+        let id = self.node(&format!("Class {}", stmt.name.lexeme));
+        if let Some(ref superclass) = stmt.superclass {
+            self.expr(id, superclass, "superclass");
+        }
+        self.stmts(id, &stmt.methods);
+        id
+    }
+}
@@ -0,0 +1,273 @@
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+
+use crate::{expr::*, object::Object, stmt::*, token::Token};
+
+/// Serializes syntax trees to JSON for tools outside the interpreter.
+///
+/// Every node is an object whose `"type"` is the node's name, such as
+/// `"Binary"` or `"While"`. Tokens become `{"lexeme": ..., "line": ...}`, and
+/// literals carry a `"line"` too, so anything that came from the source can
+/// be traced back to its line. Numbers JSON can't hold are written as the
+/// strings `"inf"`, `"-inf"` and `"NaN"`.
+#[derive(Debug)]
+pub struct AstJson;
+
+impl AstJson {
+    pub fn program(&mut self, statements: &[Stmt]) -> Value {
+        json!({
+            "type": "Program",
+            "body": self.stmts(statements),
+        })
+    }
+
+    pub fn expr(&mut self, expr: &Expr) -> Value {
+        expr.accept(self)
+    }
+
+    pub fn stmt(&mut self, stmt: &Stmt) -> Value {
+        stmt.accept(self)
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Value {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) -> Value {
+        statements.iter().map(|stmt| self.stmt(stmt)).collect()
+    }
+
+    fn token(token: &Token) -> Value {
+        json!({ "lexeme": token.lexeme, "line": token.line })
+    }
+}
+
+impl ExprVisitor<Value> for AstJson {
+    fn visit_literal_expr(&mut self, expr: &ExprLiteral) -> Value {
+        let value = match expr.value {
+            Object::Bool(b) => json!(b),
+            Object::Num(n) if n.is_finite() => json!(n),
+            Object::Num(n) => json!(n.to_string()),
+            Object::Str(ref s) => json!(s),
+            // The parser only makes literals out of the above and `nil`.
+            _ => Value::Null,
+        };
+        json!({ "type": "Literal", "value": value, "line": expr.line })
+    }
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) -> Value {
+        json!({
+            "type": "Unary",
+            "operator": Self::token(&expr.operator),
+            "right": self.expr(&expr.right),
+        })
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) -> Value {
+        json!({
+            "type": "Binary",
+            "left": self.expr(&expr.left),
+            "operator": Self::token(&expr.operator),
+            "right": self.expr(&expr.right),
+        })
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) -> Value {
+        json!({ "type": "Grouping", "expression": self.expr(&expr.expression) })
+    }
+
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) -> Value {
+        json!({ "type": "Variable", "name": Self::token(&expr.name) })
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) -> Value {
+        json!({
+            "type": "Assign",
+            "name": Self::token(&expr.name),
+            "value": self.expr(&expr.value),
+        })
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) -> Value {
+        json!({
+            "type": "Logical",
+            "left": self.expr(&expr.left),
+            "operator": Self::token(&expr.operator),
+            "right": self.expr(&expr.right),
+        })
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) -> Value {
+        json!({
+            "type": "Call",
+            "callee": self.expr(&expr.callee),
+            "paren": Self::token(&expr.paren),
+            "arguments": self.exprs(&expr.arguments),
+        })
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) -> Value {
+        json!({
+            "type": "Get",
+            "object": self.expr(&expr.object),
+            "name": Self::token(&expr.name),
+        })
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) -> Value {
+        json!({
+            "type": "Set",
+            "object": self.expr(&expr.object),
+            "name": Self::token(&expr.name),
+            "value": self.expr(&expr.value),
+        })
+    }
+
+    fn visit_this_expr(&mut self, expr: &ExprThis) -> Value {
+        json!({ "type": "This", "keyword": Self::token(&expr.keyword) })
+    }
+
+    fn visit_super_expr(&mut self, expr: &ExprSuper) -> Value {
+        json!({
+            "type": "Super",
+            "keyword": Self::token(&expr.keyword),
+            "method": Self::token(&expr.method),
+        })
+    }
+}
+
+impl StmtVisitor<Value> for AstJson {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) -> Value {
+        json!({ "type": "Expression", "expression": self.expr(&stmt.expression) })
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) -> Value {
+        json!({
+            "type": "Print",
+            "keyword": Self::token(&stmt.keyword),
+            "expression": self.expr(&stmt.expression),
+        })
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) -> Value {
+        json!({
+            "type": "Var",
+            "name": Self::token(&stmt.name),
+            "initializer": stmt.initializer.as_ref().map(|expr| self.expr(expr)),
+        })
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) -> Value {
+        json!({ "type": "Block", "statements": self.stmts(&stmt.statements) })
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) -> Value {
+        json!({
+            "type": "If",
+            "keyword": Self::token(&stmt.keyword),
+            "condition": self.expr(&stmt.condition),
+            "then_branch": self.stmt(&stmt.then_branch),
+            "else_branch": stmt.else_branch.as_ref().map(|stmt| self.stmt(stmt)),
+        })
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) -> Value {
+        json!({
+            "type": "While",
+            "keyword": Self::token(&stmt.keyword),
+            "condition": self.expr(&stmt.condition),
+            "body": self.stmt(&stmt.body),
+        })
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) -> Value {
+        let params: Vec<Value> = stmt.params.iter().map(Self::token).collect();
+        json!({
+            "type": "Function",
+            "name": Self::token(&stmt.name),
+            "params": params,
+            "body": self.stmts(&stmt.body),
+        })
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) -> Value {
+        json!({
+            "type": "Return",
+            "keyword": Self::token(&stmt.keyword),
+            "value": stmt.value.as_ref().map(|expr| self.expr(expr)),
+        })
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) -> Value {
+        json!({
+            "type": "Class",
+            "name": Self::token(&stmt.name),
+            "superclass": stmt.superclass.as_ref().map(|expr| self.expr(expr)),
+            "methods": self.stmts(&stmt.methods),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    #[test]
+    fn serializes_statements_with_token_lines() {
+        let tokens = Scanner::new("var a = 1;\nprint -a;").scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        assert_eq!(
+            AstJson.program(&statements),
+            json!({
+                "type": "Program",
+                "body": [
+                    {
+                        "type": "Var",
+                        "name": { "lexeme": "a", "line": 1 },
+                        "initializer": { "type": "Literal", "value": 1.0, "line": 1 },
+                    },
+                    {
+                        "type": "Print",
+                        "keyword": { "lexeme": "print", "line": 2 },
+                        "expression": {
+                            "type": "Unary",
+                            "operator": { "lexeme": "-", "line": 2 },
+                            "right": {
+                                "type": "Variable",
+                                "name": { "lexeme": "a", "line": 2 },
+                            },
+                        },
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_keywords_and_numbers_json_cannot_hold() {
+        let tokens = Scanner::new("if (true)\n  while (false) nil;")
+            .scan_tokens()
+            .unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        let json = AstJson.program(&statements);
+        assert_eq!(
+            json["body"][0]["keyword"],
+            json!({ "lexeme": "if", "line": 1 })
+        );
+        assert_eq!(
+            json["body"][0]["then_branch"]["keyword"],
+            json!({ "lexeme": "while", "line": 2 })
+        );
+
+        let infinity = Expr::new_literal(Object::Num(f64::NEG_INFINITY), None);
+        assert_eq!(
+            AstJson.expr(&infinity),
+            json!({ "type": "Literal", "value": "-inf", "line": null })
+        );
+    }
+}
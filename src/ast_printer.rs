@@ -1,7 +1,8 @@
-#![allow(dead_code)]
+use std::rc::Rc;
 
-use crate::expr::*;
+use crate::{expr::*, stmt::*};
 
+/// Prints syntax trees as S-expressions, in the style of the book.
 #[derive(Debug)]
 pub struct AstPrinter;
 
@@ -10,6 +11,29 @@ impl AstPrinter {
         expr.accept(self)
     }
 
+    /// Prints a whole program, one top-level statement per line. Statements
+    /// that contain other statements put each of them on its own indented line.
+    pub fn print_program(&mut self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|stmt| stmt.accept(self) + "\n")
+            .collect()
+    }
+
+    fn nest(&mut self, header: &str, statements: &[&Stmt]) -> String {
+        let mut builder = format!("({}", header);
+
+        for stmt in statements {
+            for line in stmt.accept(self).lines() {
+                builder.push_str("\n  ");
+                builder.push_str(line);
+            }
+        }
+        builder.push(')');
+
+        builder
+    }
+
     fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
         let mut builder = String::new();
 
@@ -42,36 +66,102 @@ impl ExprVisitor<String> for AstPrinter {
         self.parenthesize("group", &[&*expr.expression])
     }
 
-    fn visit_variable_expr(&mut self, _expr: &ExprVariable) -> String {
-        unimplemented!();
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) -> String {
+        expr.name.lexeme.clone()
     }
 
-    fn visit_assign_expr(&mut self, _expr: &ExprAssign) -> String {
-        unimplemented!();
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) -> String {
+        self.parenthesize(&format!("= {}", expr.name.lexeme), &[&*expr.value])
     }
 
-    fn visit_logical_expr(&mut self, _expr: &ExprLogical) -> String {
-        unimplemented!();
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) -> String {
+        self.parenthesize(&expr.operator.lexeme, &[&*expr.left, &*expr.right])
     }
 
-    fn visit_call_expr(&mut self, _expr: &ExprCall) -> String {
-        unimplemented!();
+    fn visit_call_expr(&mut self, expr: &ExprCall) -> String {
+        let mut exprs = vec![&*expr.callee];
+        exprs.extend(expr.arguments.iter());
+        self.parenthesize("call", &exprs)
     }
 
-    fn visit_get_expr(&mut self, _expr: &ExprGet) -> String {
-        unimplemented!();
+    fn visit_get_expr(&mut self, expr: &ExprGet) -> String {
+        self.parenthesize(&format!(". {}", expr.name.lexeme), &[&*expr.object])
     }
 
-    fn visit_set_expr(&mut self, _expr: &ExprSet) -> String {
-        unimplemented!();
+    fn visit_set_expr(&mut self, expr: &ExprSet) -> String {
+        self.parenthesize(
+            &format!("set {}", expr.name.lexeme),
+            &[&*expr.object, &*expr.value],
+        )
     }
 
     fn visit_this_expr(&mut self, _expr: &ExprThis) -> String {
-        unimplemented!();
+        "this".to_string()
+    }
+
+    fn visit_super_expr(&mut self, expr: &ExprSuper) -> String {
+        format!("(super {})", expr.method.lexeme)
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) -> String {
+        self.parenthesize(";", &[&stmt.expression])
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) -> String {
+        self.parenthesize("print", &[&stmt.expression])
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) -> String {
+        let header = format!("var {}", stmt.name.lexeme);
+        match stmt.initializer {
+            Some(ref initializer) => self.parenthesize(&header, &[initializer]),
+            None => format!("({})", header),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) -> String {
+        let statements: Vec<&Stmt> = stmt.statements.iter().collect();
+        self.nest("block", &statements)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) -> String {
+        let header = format!("if {}", self.print(&stmt.condition));
+        match stmt.else_branch {
+            Some(ref else_branch) => self.nest(&header, &[&stmt.then_branch, else_branch]),
+            None => self.nest(&header, &[&stmt.then_branch]),
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) -> String {
+        let header = format!("while {}", self.print(&stmt.condition));
+        self.nest(&header, &[&stmt.body])
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) -> String {
+        let params: Vec<&str> = stmt.params.iter().map(|p| p.lexeme.as_str()).collect();
+        let header = format!("fun {} ({})", stmt.name.lexeme, params.join(" "));
+        let body: Vec<&Stmt> = stmt.body.iter().collect();
+        self.nest(&header, &body)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) -> String {
+        match stmt.value {
+            Some(ref value) => self.parenthesize("return", &[value]),
+            None => "(return)".to_string(),
+        }
     }
 
-    fn visit_super_expr(&mut self, _expr: &ExprSuper) -> String {
-        unimplemented!();
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) -> String {
+        let header = match stmt.superclass {
+            Some(ref superclass) => {
+                format!("class {} < {}", stmt.name.lexeme, self.print(superclass))
+            }
+            None => format!("class {}", stmt.name.lexeme),
+        };
+        let methods: Vec<&Stmt> = stmt.methods.iter().collect();
+        self.nest(&header, &methods)
     }
 }
 
@@ -85,10 +175,10 @@ mod test {
     fn astprinter_books_example() {
         let left = Expr::new_unary(
             Token::new(Minus, "-".into(), Object::Null, 1),
-            Expr::new_literal(Object::Num(123f64)),
+            Expr::new_literal(Object::Num(123f64), None),
         );
         let op = Token::new(Star, "*".into(), Object::Null, 1);
-        let right = Expr::new_grouping(Expr::new_literal(Object::Num(45.67f64)));
+        let right = Expr::new_grouping(Expr::new_literal(Object::Num(45.67f64), None));
 
         let expression = Expr::new_binary(left, op, right);
 
@@ -0,0 +1,9 @@
+//! The Lox debug adapter. Editors start it and talk to it over stdio.
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    let code =
+        jlox_rs::limits::with_stack(|| jlox_rs::dap::run(io::stdin().lock(), io::stdout().lock()))?;
+    std::process::exit(code)
+}
@@ -0,0 +1,8 @@
+//! The Lox language server. Editors start it and talk to it over stdio.
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    let code = jlox_rs::lsp::run(io::stdin().lock(), io::stdout().lock())?;
+    std::process::exit(code)
+}
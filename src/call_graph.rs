@@ -0,0 +1,382 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{expr::*, scopes::Scopes, stmt::*, token::Token};
+
+/// Node for code outside of any function.
+const SCRIPT: &str = "<script>";
+
+#[derive(Debug, Default)]
+struct Class {
+    superclass: Option<String>,
+    methods: HashMap<String, String>,
+}
+
+/// Builds a static call graph as a Graphviz `digraph`: an edge from one
+/// function to another whenever its body names the other, calls `this.m` or
+/// `super.m`, plus a dashed edge from every class to its superclass.
+///
+/// Names are looked up the way the `Resolver` resolved them. Methods on any
+/// other object are dynamic and left out.
+#[derive(Debug)]
+pub struct CallGraph<'a> {
+    globals: HashMap<String, String>,
+    /// What each local name refers to, when it is a function or class.
+    scopes: Scopes<'a, Option<String>>,
+    classes: HashMap<String, Class>,
+    /// Enclosing functions, innermost last.
+    functions: Vec<String>,
+    /// Enclosing classes, innermost last.
+    current_classes: Vec<String>,
+    nodes: Vec<String>,
+    edges: Vec<String>,
+    seen: HashSet<String>,
+}
+
+impl<'a> CallGraph<'a> {
+    pub fn new(locals: &'a HashMap<Expr, usize>) -> Self {
+        Self {
+            globals: HashMap::new(),
+            scopes: Scopes::new(locals),
+            classes: HashMap::new(),
+            functions: vec![],
+            current_classes: vec![],
+            nodes: vec![],
+            edges: vec![],
+            seen: HashSet::new(),
+        }
+    }
+
+    pub fn print(mut self, statements: &[Stmt]) -> String {
+        // Globals are late bound, so a function may call one declared after it.
+        for stmt in statements {
+            match stmt {
+                Stmt::Function(function) => {
+                    let name = &function.name.lexeme;
+                    self.globals.insert(name.clone(), name.clone());
+                }
+                Stmt::Class(class) => {
+                    let name = &class.name.lexeme;
+                    self.globals.insert(name.clone(), name.clone());
+                }
+                Stmt::Var(var) => {
+                    self.globals.remove(&var.name.lexeme);
+                }
+                _ => (),
+            }
+        }
+
+        self.nodes
+            .push(format!("\"{}\" [shape=plaintext];", SCRIPT));
+        self.stmts(statements);
+
+        let mut builder = "digraph calls {\n".to_string();
+        for line in self.nodes.iter().chain(&self.edges) {
+            builder.push_str("  ");
+            builder.push_str(line);
+            builder.push('\n');
+        }
+        builder.push_str("}\n");
+
+        builder
+    }
+
+    /// Names a function or class declared at the current position. Nested
+    /// declarations are qualified with the function they are in.
+    fn declare_node(&mut self, name: &Token) -> String {
+        let mut node = match self.functions.last() {
+            Some(function) => format!("{}.{}", function, name.lexeme),
+            None => name.lexeme.clone(),
+        };
+        // Two blocks may declare functions with the same name.
+        if !self.scopes.is_empty() && self.seen.contains(&node) {
+            node = format!("{} (line {})", node, name.line);
+        }
+        self.seen.insert(node.clone());
+        node
+    }
+
+    fn declare(&mut self, name: &Token, node: Option<String>) {
+        self.scopes.declare(&name.lexeme, node);
+    }
+
+    fn lookup(&self, expr: &Expr, name: &Token) -> Option<String> {
+        if self.scopes.is_local(expr) {
+            self.scopes.get(expr, name).cloned().flatten()
+        } else {
+            self.globals.get(&name.lexeme).cloned()
+        }
+    }
+
+    /// Finds `method` on `class` or the nearest superclass that has it.
+    fn find_method(&self, class: Option<&String>, method: &str) -> Option<String> {
+        let mut class = class;
+        while let Some(name) = class {
+            let info = self.classes.get(name)?;
+            if let Some(node) = info.methods.get(method) {
+                return Some(node.clone());
+            }
+            class = info.superclass.as_ref();
+        }
+        None
+    }
+
+    // Redeclaring a global reuses its node and edges.
+    fn add_node(&mut self, line: String) {
+        if !self.nodes.contains(&line) {
+            self.nodes.push(line);
+        }
+    }
+
+    fn add_edge(&mut self, line: String) {
+        if !self.edges.contains(&line) {
+            self.edges.push(line);
+        }
+    }
+
+    fn reference(&mut self, callee: Option<String>) {
+        if let Some(callee) = callee {
+            let caller = self.functions.last().map_or(SCRIPT, String::as_str);
+            let edge = format!("\"{}\" -> \"{}\";", caller, callee);
+            self.add_edge(edge);
+        }
+    }
+
+    fn function(&mut self, node: String, function: &StmtFunction) {
+        self.add_node(format!("\"{}\";", node));
+        self.functions.push(node);
+        self.scopes.begin();
+        for param in &function.params {
+            self.declare(param, None);
+        }
+        self.stmts(&function.body);
+        self.scopes.end();
+        self.functions.pop();
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            stmt.accept(self);
+        }
+    }
+}
+
+impl ExprVisitor<()> for CallGraph<'_> {
+    fn visit_literal_expr(&mut self, _expr: &ExprLiteral) {}
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) {
+        self.expr(&expr.right);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) {
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) {
+        self.expr(&expr.expression);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) {
+        let callee = self.lookup(&Expr::Variable(expr.clone()), &expr.name);
+        self.reference(callee);
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) {
+        self.expr(&expr.value);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) {
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) {
+        self.expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.expr(argument);
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) {
+        if let Expr::This(_) = *expr.object {
+            let callee = self.find_method(self.current_classes.last(), &expr.name.lexeme);
+            self.reference(callee);
+        }
+        self.expr(&expr.object);
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) {
+        self.expr(&expr.object);
+        self.expr(&expr.value);
+    }
+
+    fn visit_this_expr(&mut self, _expr: &ExprThis) {}
+
+    fn visit_super_expr(&mut self, expr: &ExprSuper) {
+        let superclass = self
+            .current_classes
+            .last()
+            .and_then(|class| self.classes.get(class))
+            .and_then(|class| class.superclass.as_ref());
+        let callee = self.find_method(superclass, &expr.method.lexeme);
+        self.reference(callee);
+    }
+}
+
+impl StmtVisitor<()> for CallGraph<'_> {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) {
+        if let Some(ref initializer) = stmt.initializer {
+            self.expr(initializer);
+        }
+        self.declare(&stmt.name, None);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) {
+        self.scopes.begin();
+        self.stmts(&stmt.statements);
+        self.scopes.end();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) {
+        self.expr(&stmt.condition);
+        stmt.then_branch.accept(self);
+        if let Some(ref else_branch) = stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) {
+        self.expr(&stmt.condition);
+        stmt.body.accept(self);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) {
+        let node = self.declare_node(&stmt.name);
+        self.declare(&stmt.name, Some(node.clone()));
+        self.function(node, stmt);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) {
+        if let Some(ref value) = stmt.value {
+            self.expr(value);
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) {
+        let node = self.declare_node(&stmt.name);
+        self.declare(&stmt.name, Some(node.clone()));
+        self.add_node(format!("\"{}\" [shape=box];", node));
+
+        let superclass = match stmt.superclass {
+            Some(Expr::Variable(ref variable)) => {
+                self.lookup(&Expr::Variable(variable.clone()), &variable.name)
+            }
+            _ => None,
+        };
+        if let Some(ref superclass) = superclass {
+            self.add_edge(format!(
+                "\"{}\" -> \"{}\" [style=dashed, label=\"inherits\"];",
+                node, superclass
+            ));
+        }
+
+        // Register every method first; they may call each other in any order.
+        let methods = stmt
+            .methods
+            .iter()
+            .filter_map(|method| match method {
+                Stmt::Function(function) => Some(function),
+                _ => None,
+            })
+            .map(|function| {
+                let name = function.name.lexeme.clone();
+                (name.clone(), format!("{}.{}", node, name))
+            })
+            .collect();
+        self.classes.insert(
+            node.clone(),
+            Class {
+                superclass,
+                methods,
+            },
+        );
+
+        if stmt.superclass.is_some() {
+            self.scopes.begin();
+            self.scopes.declare("super", None);
+        }
+        self.scopes.begin();
+        self.scopes.declare("this", None);
+        self.current_classes.push(node.clone());
+
+        for method in &stmt.methods {
+            if let Stmt::Function(function) = method {
+                self.function(format!("{}.{}", node, function.name.lexeme), function);
+            }
+        }
+
+        self.current_classes.pop();
+        self.scopes.end();
+        if stmt.superclass.is_some() {
+            self.scopes.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{interpreter::Interpreter, parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    use super::*;
+
+    #[test]
+    fn links_functions_methods_and_superclasses() {
+        let source = "
+            fun helper() {}
+            class A { init() { this.setup(); } setup() { helper(); } }
+            class B < A { setup() { super.setup(); } }
+            fun main() {
+              fun inner() { return B(); }
+              var unused = 1;
+              inner();
+            }
+            main();
+        ";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter)
+            .resolve(&statements)
+            .unwrap();
+
+        let dot = CallGraph::new(&interpreter.locals).print(&statements);
+        let edges: Vec<&str> = dot.lines().filter(|l| l.contains("->")).collect();
+        assert_eq!(
+            edges,
+            vec![
+                "  \"A.init\" -> \"A.setup\";",
+                "  \"A.setup\" -> \"helper\";",
+                "  \"B\" -> \"A\" [style=dashed, label=\"inherits\"];",
+                "  \"B.setup\" -> \"A.setup\";",
+                "  \"main.inner\" -> \"B\";",
+                "  \"main\" -> \"main.inner\";",
+                "  \"<script>\" -> \"main\";",
+            ]
+        );
+    }
+}
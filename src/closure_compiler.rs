@@ -0,0 +1,604 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    error::LoxError,
+    expr::*,
+    interpreter::{check_number_operand, Interpreter},
+    lox_callable::*,
+    object::Object,
+    stmt::*,
+    token::Token,
+    token_type::TokenType,
+};
+
+pub type CompiledExpr = Box<dyn Fn(&mut Interpreter) -> Result<Object, LoxError>>;
+pub type CompiledStmt = Box<dyn Fn(&mut Interpreter) -> Result<(), LoxError>>;
+
+/// A list of statements that have already been turned into closures.
+pub struct CompiledBlock(Vec<CompiledStmt>);
+
+impl CompiledBlock {
+    pub fn iter(&self) -> std::slice::Iter<'_, CompiledStmt> {
+        self.0.iter()
+    }
+}
+
+impl std::fmt::Debug for CompiledBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CompiledBlock({} statements)", self.0.len())
+    }
+}
+
+/// Compiles a resolved program into closures.
+///
+/// Everything the tree-walker decides on each evaluation (which operator to
+/// apply, whether a variable is local and at which depth) is decided here once.
+#[derive(Debug)]
+pub struct ClosureCompiler<'a> {
+    locals: &'a HashMap<Expr, usize>,
+}
+
+impl<'a> ClosureCompiler<'a> {
+    pub fn new(locals: &'a HashMap<Expr, usize>) -> Self {
+        Self { locals }
+    }
+
+    pub fn compile(&mut self, statements: &[Stmt]) -> CompiledBlock {
+        CompiledBlock(statements.iter().map(|s| self.compile_stmt(s)).collect())
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) -> CompiledStmt {
+        stmt.accept(self)
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) -> CompiledExpr {
+        expr.accept(self)
+    }
+
+    fn compile_function(&mut self, function: &StmtFunction) -> Rc<CompiledBlock> {
+        Rc::new(self.compile(&function.body))
+    }
+
+    fn compile_lookup(&self, expr: Expr, name: &Token) -> CompiledExpr {
+        let name = name.clone();
+
+        match self.locals.get(&expr) {
+            Some(&distance) => Box::new(move |interpreter| {
+                Ok(interpreter
+                    .environment
+                    .as_ref()
+                    .borrow()
+                    .get_at(distance, &name.lexeme))
+            }),
+            None => Box::new(move |interpreter| interpreter.globals.as_ref().borrow().get(&name)),
+        }
+    }
+}
+
+macro_rules! number_operands {
+    ($left:ident, $right:ident, $operator:ident, |$a:ident, $b:ident| $result:expr) => {
+        Box::new(
+            move |interpreter| match ($left(interpreter)?, $right(interpreter)?) {
+                (Object::Num($a), Object::Num($b)) => Ok($result),
+                _ => Err(LoxError::RuntimeError(
+                    $operator.clone(),
+                    "Operands must be numbers.".into(),
+                )),
+            },
+        )
+    };
+}
+
+impl<'a> ExprVisitor<CompiledExpr> for ClosureCompiler<'a> {
+    fn visit_literal_expr(&mut self, expr: &ExprLiteral) -> CompiledExpr {
+        let value = expr.value.clone();
+        Box::new(move |_| Ok(value.clone()))
+    }
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) -> CompiledExpr {
+        let right = self.compile_expr(&expr.right);
+        let operator = expr.operator.clone();
+
+        match operator.typ {
+            TokenType::Bang => Box::new(move |interpreter| Ok(!right(interpreter)?)),
+            TokenType::Minus => Box::new(move |interpreter| {
+                let right = right(interpreter)?;
+                check_number_operand(&operator, &right)?;
+                Ok(-right)
+            }),
+            _ => unreachable!(),
+        }
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) -> CompiledExpr {
+        let left = self.compile_expr(&expr.left);
+        let right = self.compile_expr(&expr.right);
+        let operator = expr.operator.clone();
+
+        match operator.typ {
+            TokenType::Greater => {
+                number_operands!(left, right, operator, |a, b| Object::Bool(a > b))
+            }
+            TokenType::GreaterEqual => {
+                number_operands!(left, right, operator, |a, b| Object::Bool(a >= b))
+            }
+            TokenType::Less => number_operands!(left, right, operator, |a, b| Object::Bool(a < b)),
+            TokenType::LessEqual => {
+                number_operands!(left, right, operator, |a, b| Object::Bool(a <= b))
+            }
+            TokenType::BangEqual => Box::new(move |interpreter| {
+                Ok(Object::Bool(left(interpreter)? != right(interpreter)?))
+            }),
+            TokenType::EqualEqual => Box::new(move |interpreter| {
+                Ok(Object::Bool(left(interpreter)? == right(interpreter)?))
+            }),
+            TokenType::Minus => number_operands!(left, right, operator, |a, b| Object::Num(a - b)),
+            TokenType::Plus => {
+                Box::new(
+                    move |interpreter| match (left(interpreter)?, right(interpreter)?) {
+                        (Object::Num(a), Object::Num(b)) => Ok(Object::Num(a + b)),
+                        (Object::Str(mut a), Object::Str(b)) => {
+                            interpreter.limits.allocate(a.len() + b.len())?;
+                            a.push_str(&b);
+                            Ok(Object::Str(a))
+                        }
+                        _ => Err(LoxError::RuntimeError(
+                            operator.clone(),
+                            "Operands must be two numbers or two strings.".into(),
+                        )),
+                    },
+                )
+            }
+            TokenType::Slash => number_operands!(left, right, operator, |a, b| Object::Num(a / b)),
+            TokenType::Star => number_operands!(left, right, operator, |a, b| Object::Num(a * b)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) -> CompiledExpr {
+        self.compile_expr(&expr.expression)
+    }
+
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) -> CompiledExpr {
+        self.compile_lookup(Expr::Variable(expr.clone()), &expr.name)
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) -> CompiledExpr {
+        let value = self.compile_expr(&expr.value);
+        let name = expr.name.clone();
+
+        match self.locals.get(&Expr::Assign(expr.clone())) {
+            Some(&distance) => Box::new(move |interpreter| {
+                let value = value(interpreter)?;
+                interpreter.environment.as_ref().borrow_mut().assign_at(
+                    distance,
+                    &name,
+                    value.clone(),
+                );
+                Ok(value)
+            }),
+            None => Box::new(move |interpreter| {
+                let value = value(interpreter)?;
+                interpreter
+                    .globals
+                    .as_ref()
+                    .borrow_mut()
+                    .assign(&name, value.clone())?;
+                Ok(value)
+            }),
+        }
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) -> CompiledExpr {
+        let left = self.compile_expr(&expr.left);
+        let right = self.compile_expr(&expr.right);
+
+        if expr.operator.typ == TokenType::Or {
+            Box::new(move |interpreter| {
+                let left = left(interpreter)?;
+                if left.is_truthy() {
+                    return Ok(left);
+                }
+                right(interpreter)
+            })
+        } else {
+            Box::new(move |interpreter| {
+                let left = left(interpreter)?;
+                if !left.is_truthy() {
+                    return Ok(left);
+                }
+                right(interpreter)
+            })
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) -> CompiledExpr {
+        let callee = self.compile_expr(&expr.callee);
+        let arguments: Vec<_> = expr
+            .arguments
+            .iter()
+            .map(|argument| self.compile_expr(argument))
+            .collect();
+        let paren = expr.paren.clone();
+
+        Box::new(move |interpreter| {
+            let callee = callee(interpreter)?;
+
+            let mut values = Vec::with_capacity(arguments.len());
+            for argument in &arguments {
+                values.push(argument(interpreter)?);
+            }
+
+            let function = match callee {
+                Object::Callable(f) => f,
+                _ => {
+                    return Err(LoxError::RuntimeError(
+                        paren.clone(),
+                        "Can only call functions and classes.".to_string(),
+                    ))
+                }
+            };
+
+            if values.len() != function.arity() {
+                return Err(LoxError::RuntimeError(
+                    paren.clone(),
+                    format!(
+                        "Expected {} arguments but got {}.",
+                        function.arity(),
+                        values.len()
+                    ),
+                ));
+            }
+
+            interpreter.call_line = paren.line;
+            function.call(interpreter, &values)
+        })
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) -> CompiledExpr {
+        let object = self.compile_expr(&expr.object);
+        let name = expr.name.clone();
+
+        Box::new(move |interpreter| {
+            if let Object::Instance(instance) = object(interpreter)? {
+                return instance.get(&name);
+            }
+
+            Err(LoxError::RuntimeError(
+                name.clone(),
+                "Only instances have properties.".to_string(),
+            ))
+        })
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) -> CompiledExpr {
+        let object = self.compile_expr(&expr.object);
+        let value = self.compile_expr(&expr.value);
+        let name = expr.name.clone();
+
+        Box::new(move |interpreter| {
+            let mut instance = match object(interpreter)? {
+                Object::Instance(instance) => instance,
+                _ => {
+                    return Err(LoxError::RuntimeError(
+                        name.clone(),
+                        "Only instances have fields.".to_string(),
+                    ))
+                }
+            };
+
+            let value = value(interpreter)?;
+            instance.set(name.clone(), value.clone());
+
+            Ok(value)
+        })
+    }
+
+    fn visit_this_expr(&mut self, expr: &ExprThis) -> CompiledExpr {
+        self.compile_lookup(Expr::This(expr.clone()), &expr.keyword)
+    }
+
+    fn visit_super_expr(&mut self, expr: &ExprSuper) -> CompiledExpr {
+        let distance = *self.locals.get(&Expr::Super(expr.clone())).unwrap();
+        let method = expr.method.clone();
+
+        Box::new(move |interpreter| {
+            let environment = interpreter.environment.as_ref().borrow();
+            let superclass = environment.get_at(distance, "super");
+            let object = environment.get_at(distance - 1, "this");
+
+            let function = match superclass {
+                Object::Callable(CallableKind::Class(ref lox_class)) => {
+                    lox_class.find_method(&method.lexeme)
+                }
+                _ => panic!("'super' must be LoxClass."),
+            };
+
+            match (function, object) {
+                (Some(function), Object::Instance(instance)) => Ok(Object::Callable(
+                    CallableKind::Function(function.bind(instance)),
+                )),
+                (Some(_), _) => panic!("This object must be LoxInstance"),
+                (None, _) => Err(LoxError::RuntimeError(
+                    method.clone(),
+                    format!("Undefined property '{}'.", method.lexeme),
+                )),
+            }
+        })
+    }
+}
+
+impl<'a> StmtVisitor<CompiledStmt> for ClosureCompiler<'a> {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) -> CompiledStmt {
+        let expression = self.compile_expr(&stmt.expression);
+        Box::new(move |interpreter| expression(interpreter).map(|_| ()))
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) -> CompiledStmt {
+        let expression = self.compile_expr(&stmt.expression);
+        Box::new(move |interpreter| {
+            let value = expression(interpreter)?;
+            interpreter.print(&value)
+        })
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) -> CompiledStmt {
+        let initializer = stmt
+            .initializer
+            .as_ref()
+            .map(|initializer| self.compile_expr(initializer));
+        let name = stmt.name.lexeme.clone();
+
+        Box::new(move |interpreter| {
+            let value = match initializer {
+                Some(ref initializer) => initializer(interpreter)?,
+                None => Object::Null,
+            };
+
+            interpreter
+                .environment
+                .as_ref()
+                .borrow_mut()
+                .define(name.clone(), value);
+
+            Ok(())
+        })
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) -> CompiledStmt {
+        let statements = self.compile(&stmt.statements);
+
+        Box::new(move |interpreter| {
+            let environment = Rc::new(RefCell::new(Environment::new(Some(
+                interpreter.environment.clone(),
+            ))));
+            interpreter.execute_compiled_block(&statements, environment)
+        })
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) -> CompiledStmt {
+        let condition = self.compile_expr(&stmt.condition);
+        let then_branch = self.compile_stmt(&stmt.then_branch);
+        let else_branch = stmt
+            .else_branch
+            .as_ref()
+            .map(|else_branch| self.compile_stmt(else_branch));
+
+        Box::new(move |interpreter| {
+            if condition(interpreter)?.is_truthy() {
+                then_branch(interpreter)
+            } else if let Some(ref else_branch) = else_branch {
+                else_branch(interpreter)
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) -> CompiledStmt {
+        let condition = self.compile_expr(&stmt.condition);
+        let body = self.compile_stmt(&stmt.body);
+
+        Box::new(move |interpreter| {
+            while condition(interpreter)?.is_truthy() {
+                body(interpreter)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) -> CompiledStmt {
+        let body = self.compile_function(stmt);
+        let declaration = stmt.clone();
+
+        Box::new(move |interpreter| {
+            let function = LoxFunction::new_compiled(
+                declaration.clone(),
+                body.clone(),
+                interpreter.environment.clone(),
+                false,
+            );
+            interpreter.environment.as_ref().borrow_mut().define(
+                declaration.name.lexeme.clone(),
+                Object::Callable(CallableKind::Function(function)),
+            );
+            Ok(())
+        })
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) -> CompiledStmt {
+        let value = stmt.value.as_ref().map(|value| self.compile_expr(value));
+
+        Box::new(move |interpreter| {
+            let value = match value {
+                Some(ref value) => value(interpreter)?,
+                None => Object::Null,
+            };
+            Err(LoxError::Return(value))
+        })
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) -> CompiledStmt {
+        let superclass = stmt.superclass.as_ref().map(|superclass| {
+            let name = match superclass {
+                Expr::Variable(variable) => variable.name.clone(),
+                _ => panic!("StmtClass.superclass must be ExprVariable."),
+            };
+            (self.compile_expr(superclass), name)
+        });
+
+        let methods: Vec<_> = stmt
+            .methods
+            .iter()
+            .map(|method| match method {
+                Stmt::Function(function) => (function.clone(), self.compile_function(function)),
+                _ => panic!("StmtClass.methods must contain StmtFunction only."),
+            })
+            .collect();
+
+        let name = stmt.name.clone();
+
+        Box::new(move |interpreter| {
+            let mut superclass_class = None;
+            let mut superclass_value = Object::Null;
+
+            if let Some((ref superclass, ref superclass_name)) = superclass {
+                superclass_value = superclass(interpreter)?;
+
+                match superclass_value {
+                    Object::Callable(CallableKind::Class(ref lox_class)) => {
+                        superclass_class = Some(Rc::new(lox_class.clone()))
+                    }
+                    _ => {
+                        return Err(LoxError::RuntimeError(
+                            superclass_name.clone(),
+                            "Superclass must be a class.".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            interpreter
+                .environment
+                .as_ref()
+                .borrow_mut()
+                .define(name.lexeme.to_string(), Object::Null);
+
+            let enclosing = interpreter.environment.clone();
+            if superclass_class.is_some() {
+                let mut environment = Environment::new(Some(enclosing.clone()));
+                environment.define("super".to_string(), superclass_value);
+                interpreter.environment = Rc::new(RefCell::new(environment));
+            }
+
+            let mut functions = HashMap::<String, LoxFunction>::new();
+            for (declaration, body) in &methods {
+                let function = LoxFunction::new_compiled(
+                    declaration.clone(),
+                    body.clone(),
+                    interpreter.environment.clone(),
+                    declaration.name.lexeme == "init",
+                );
+                functions.insert(declaration.name.lexeme.to_string(), function);
+            }
+
+            let klass = LoxClass::new(name.lexeme.to_string(), superclass_class, functions);
+
+            interpreter.environment = enclosing;
+            interpreter
+                .environment
+                .as_ref()
+                .borrow_mut()
+                .assign(&name, Object::Callable(CallableKind::Class(klass)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{interpreter::ExecutionMode, parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    use super::*;
+
+    // Runs `source` and returns the value left in the global `result`.
+    fn run(source: &str, mode: ExecutionMode) -> Result<Object, LoxError> {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_execution_mode(mode);
+
+        let tokens = Scanner::new(source).scan_tokens()?;
+        let statements = Parser::new(tokens).parse()?;
+        Resolver::new(&mut interpreter).resolve(&statements)?;
+        interpreter.interpret(&statements)?;
+
+        let result = Token::new(TokenType::Identifier, "result".into(), Object::Null, 0);
+        let value = interpreter.globals.as_ref().borrow().get(&result);
+        value
+    }
+
+    fn assert_same_result(source: &str, expected: Object) {
+        assert_eq!(run(source, ExecutionMode::TreeWalk), Ok(expected.clone()));
+        assert_eq!(run(source, ExecutionMode::Closure), Ok(expected));
+    }
+
+    #[test]
+    fn closure_backend_matches_tree_walker_on_expressions() {
+        assert_same_result("var result = (1 + 2) * 3 - 4 / 2;", Object::Num(7f64));
+        assert_same_result(
+            "var result = !(1 >= 2) and nil or \"x\";",
+            Object::Str("x".into()),
+        );
+        assert_same_result("var result = \"a\" + \"b\" == \"ab\";", Object::Bool(true));
+    }
+
+    #[test]
+    fn closure_backend_matches_tree_walker_on_loops_and_closures() {
+        let source = "
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    return i;
+                }
+                return count;
+            }
+            var counter = makeCounter();
+            var result = 0;
+            for (var i = 0; i < 10; i = i + 1) {
+                if (i == 5) result = result + 100; else result = result + counter();
+            }";
+
+        assert_same_result(source, Object::Num(145f64));
+    }
+
+    #[test]
+    fn closure_backend_matches_tree_walker_on_classes() {
+        let source = "
+            class A {
+                init(n) { this.n = n; }
+                get() { return this.n; }
+            }
+            class B < A {
+                init(n) { super.init(n * 2); }
+                get() { return super.get() + 1; }
+            }
+            var result = B(20).get();";
+
+        assert_same_result(source, Object::Num(41f64));
+    }
+
+    #[test]
+    fn closure_backend_reports_runtime_errors() {
+        let source = "fun f() { return 1 + \"a\"; } var result = f();";
+
+        for mode in [ExecutionMode::TreeWalk, ExecutionMode::Closure] {
+            match run(source, mode) {
+                Err(LoxError::RuntimeError(token, message)) => {
+                    assert_eq!(token.lexeme, "+");
+                    assert_eq!(message, "Operands must be two numbers or two strings.");
+                }
+                other => panic!("expected a runtime error, got {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{collections::BTreeMap, fmt::Write, rc::Rc};
+
+use crate::{expr::*, stmt::*, token::Token};
+
+/// Which lines ran and which way each branch went, for `--coverage`.
+///
+/// Every branch point has two branches: for `if` the then and the else
+/// branch, for a loop condition entering the body and leaving the loop, and
+/// for `and`/`or` evaluating the right operand and short-circuiting.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    /// How many times a statement on each line ran.
+    lines: BTreeMap<usize, u64>,
+    /// Keyed by the line and column of the `if`, `while`, `and` or `or`.
+    branches: BTreeMap<(usize, usize), [u64; 2]>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the lines and branch points of `statements`, so that the ones
+    /// that never run show up with a count of zero.
+    pub(crate) fn register(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.stmt(stmt);
+        }
+    }
+
+    pub(crate) fn hit(&mut self, stmt: &Stmt) {
+        if let Some(line) = Self::line(stmt) {
+            *self.lines.entry(line).or_default() += 1;
+        }
+    }
+
+    /// Records that the branch point at `token` took branch `index`, 0 or 1.
+    pub(crate) fn branch(&mut self, token: &Token, index: usize) {
+        self.branches.entry((token.line, token.column)).or_default()[index] += 1;
+    }
+
+    /// Blocks only group statements; the ones inside are counted instead.
+    fn line(stmt: &Stmt) -> Option<usize> {
+        match stmt {
+            Stmt::Block(_) => None,
+            stmt => stmt.line(),
+        }
+    }
+
+    fn branch_point(&mut self, token: &Token) {
+        self.branches.entry((token.line, token.column)).or_default();
+    }
+
+    /// The report in lcov's tracefile format, for `source_file`.
+    pub fn to_lcov(&self, source_file: &str) -> String {
+        let mut lcov = format!("TN:\nSF:{}\n", source_file);
+
+        for (block, (&(line, _), taken)) in self.branches.iter().enumerate() {
+            for (branch, count) in taken.iter().enumerate() {
+                // lcov writes `-` for branches whose condition never ran.
+                let count = match taken {
+                    [0, 0] => "-".to_string(),
+                    _ => count.to_string(),
+                };
+                let _ = writeln!(lcov, "BRDA:{},{},{},{}", line, block, branch, count);
+            }
+        }
+        let branches_hit = self.branches.values().flatten().filter(|&&n| n > 0);
+        let _ = writeln!(lcov, "BRF:{}", self.branches.len() * 2);
+        let _ = writeln!(lcov, "BRH:{}", branches_hit.count());
+
+        for (line, count) in &self.lines {
+            let _ = writeln!(lcov, "DA:{},{}", line, count);
+        }
+        let lines_hit = self.lines.values().filter(|&&n| n > 0);
+        let _ = writeln!(lcov, "LF:{}", self.lines.len());
+        let _ = writeln!(lcov, "LH:{}", lines_hit.count());
+
+        lcov.push_str("end_of_record\n");
+        lcov
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        if let Some(line) = Self::line(stmt) {
+            self.lines.entry(line).or_default();
+        }
+        stmt.accept(self)
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+}
+
+impl ExprVisitor<()> for Coverage {
+    fn visit_literal_expr(&mut self, _expr: &ExprLiteral) {}
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) {
+        self.expr(&expr.right);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) {
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) {
+        self.expr(&expr.expression);
+    }
+
+    fn visit_variable_expr(&mut self, _expr: &ExprVariable) {}
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) {
+        self.expr(&expr.value);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) {
+        self.branch_point(&expr.operator);
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) {
+        self.expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.expr(argument);
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) {
+        self.expr(&expr.object);
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) {
+        self.expr(&expr.object);
+        self.expr(&expr.value);
+    }
+
+    fn visit_this_expr(&mut self, _expr: &ExprThis) {}
+
+    fn visit_super_expr(&mut self, _expr: &ExprSuper) {}
+}
+
+impl StmtVisitor<()> for Coverage {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) {
+        if let Some(ref initializer) = stmt.initializer {
+            self.expr(initializer);
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) {
+        for stmt in &stmt.statements {
+            self.stmt(stmt);
+        }
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) {
+        self.branch_point(&stmt.keyword);
+        self.expr(&stmt.condition);
+        self.stmt(&stmt.then_branch);
+        if let Some(ref else_branch) = stmt.else_branch {
+            self.stmt(else_branch);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) {
+        self.branch_point(&stmt.keyword);
+        self.expr(&stmt.condition);
+        self.stmt(&stmt.body);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) {
+        for stmt in &stmt.body {
+            self.stmt(stmt);
+        }
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) {
+        if let Some(ref value) = stmt.value {
+            self.expr(value);
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) {
+        if let Some(ref superclass) = stmt.superclass {
+            self.expr(superclass);
+        }
+        // Methods are declared with the class, not run as statements.
+        for method in &stmt.methods {
+            method.accept(self);
+        }
+    }
+}
@@ -0,0 +1,462 @@
+//! A Debug Adapter Protocol server, run by the `jlox-dap` binary.
+//!
+//! One session debugs one script on the tree-walking interpreter. Messages
+//! are framed the same way as the language server's. The program runs on
+//! the thread that reads requests, so while it is paused the `Adapter`
+//! answers requests itself, until one of them resumes the program.
+
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    debugger::{Debugger, Frontend, Pause, Reason, Resume, Value},
+    error::{self, LoxError},
+    interpreter::Interpreter,
+    lsp::{read_message, write_message},
+};
+
+/// There is only ever the one.
+const THREAD_ID: i64 = 1;
+
+/// Serves one client until it disconnects or closes the input.
+pub fn run(input: impl BufRead + 'static, output: impl Write + 'static) -> io::Result<i32> {
+    let connection = Rc::new(RefCell::new(Connection {
+        input: Box::new(input),
+        output: Box::new(output),
+        seq: 0,
+        disconnected: false,
+    }));
+    let mut launch = None;
+    let mut configured = false;
+    let mut breakpoints = vec![];
+
+    loop {
+        let Some(request) = connection.borrow_mut().receive()? else {
+            return Ok(0);
+        };
+        let arguments = &request["arguments"];
+
+        let result = match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                let capabilities = json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsEvaluateForHovers": true,
+                });
+                connection
+                    .borrow_mut()
+                    .respond(&request, Ok(capabilities))?;
+                connection.borrow_mut().event("initialized", json!({}))?;
+                continue;
+            }
+            "launch" => Launch::from_arguments(arguments).map(|arguments| {
+                launch = Some(arguments);
+                Json::Null
+            }),
+            "setBreakpoints" => {
+                breakpoints = breakpoint_lines(arguments);
+                Ok(verified(&breakpoints))
+            }
+            "configurationDone" => {
+                configured = true;
+                Ok(Json::Null)
+            }
+            "threads" => Ok(threads()),
+            "disconnect" => {
+                connection.borrow_mut().respond(&request, Ok(Json::Null))?;
+                return Ok(0);
+            }
+            command => Err(format!("'{}' needs a paused program.", command)),
+        };
+        connection.borrow_mut().respond(&request, result)?;
+
+        // The client may launch before or after it is done configuring.
+        if let Some(launch) = launch.take_if(|_| configured) {
+            let status = launch.run(&connection, &breakpoints);
+            let mut connection = connection.borrow_mut();
+            if connection.disconnected {
+                return Ok(0);
+            }
+            connection.event("exited", json!({ "exitCode": status }))?;
+            connection.event("terminated", json!({}))?;
+        }
+    }
+}
+
+struct Connection {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: i64,
+    /// Set when the client hung up or asked to disconnect while paused.
+    disconnected: bool,
+}
+
+impl Connection {
+    fn receive(&mut self) -> io::Result<Option<Json>> {
+        read_message(&mut self.input)
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Json::Null) => (),
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+/// What `print` writes, sent to the client as output events a line at a time.
+struct OutputEvents {
+    connection: Rc<RefCell<Connection>>,
+    pending: String,
+}
+
+impl Write for OutputEvents {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.push_str(&String::from_utf8_lossy(buf));
+        while let Some(end) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=end).collect();
+            let body = json!({ "category": "stdout", "output": line });
+            self.connection.borrow_mut().event("output", body)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Launch {
+    program: PathBuf,
+    source: String,
+    args: Vec<String>,
+    stop_on_entry: bool,
+}
+
+impl Launch {
+    fn from_arguments(arguments: &Json) -> Result<Self, String> {
+        let program = arguments["program"]
+            .as_str()
+            .ok_or("Missing the 'program' to debug.")?;
+        let source = std::fs::read_to_string(program)
+            .map_err(|err| format!("Could not read '{}': {}", program, err))?;
+        let args = arguments["args"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|arg| arg.as_str().map(str::to_string))
+            .collect();
+
+        Ok(Self {
+            program: PathBuf::from(program),
+            source,
+            args,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Runs the program to the end and returns its exit status, as `jlox`
+    /// would exit with.
+    fn run(self, connection: &Rc<RefCell<Connection>>, breakpoints: &[usize]) -> i32 {
+        let mut interpreter = Interpreter::new();
+        let name = self.program.display().to_string();
+        interpreter.set_args(std::iter::once(name).chain(self.args).collect());
+        interpreter.set_output(OutputEvents {
+            connection: connection.clone(),
+            pending: String::new(),
+        });
+
+        let mut debugger = Debugger::new(Adapter {
+            connection: connection.clone(),
+            program: self.program,
+            handles: vec![],
+        });
+        debugger.set_stop_on_entry(self.stop_on_entry);
+        for &line in breakpoints {
+            debugger.set_breakpoint(line);
+        }
+        interpreter.set_debugger(debugger);
+
+        let (result, diagnostics) = error::capture(|| crate::run(&self.source, &mut interpreter));
+
+        let mut errors = String::new();
+        for diagnostic in diagnostics {
+            errors.push_str(&format!(
+                "[line {}] Error: {}\n",
+                diagnostic.line, diagnostic.message
+            ));
+        }
+        let status = match result {
+            Ok(()) => 0,
+            Err(LoxError::RuntimeError(token, message)) => {
+                errors.push_str(&format!("{}\n[line {}]\n", message, token.line));
+                70
+            }
+            Err(LoxError::AssertionFailed(line, message)) => {
+                errors.push_str(&format!("Assertion failed: {}\n[line {}]\n", message, line));
+                70
+            }
+            Err(LoxError::CallDepthExceeded(line)) => {
+                errors.push_str(&format!("Stack overflow.\n[line {}]\n", line));
+                70
+            }
+            Err(LoxError::Exit(code)) => code,
+            Err(_) => 65,
+        };
+
+        let mut connection = connection.borrow_mut();
+        if !errors.is_empty() && !connection.disconnected {
+            let body = json!({ "category": "stderr", "output": errors });
+            let _ = connection.event("output", body);
+        }
+        status
+    }
+}
+
+fn breakpoint_lines(arguments: &Json) -> Vec<usize> {
+    arguments["breakpoints"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|breakpoint| breakpoint["line"].as_u64())
+        .map(|line| line as usize)
+        .collect()
+}
+
+fn verified(lines: &[usize]) -> Json {
+    let breakpoints: Vec<Json> = lines
+        .iter()
+        .map(|line| json!({ "verified": true, "line": line }))
+        .collect();
+    json!({ "breakpoints": breakpoints })
+}
+
+fn threads() -> Json {
+    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })
+}
+
+/// What a `variablesReference` points at. References are indexes into
+/// `Adapter::handles`, plus one, and only last until the program resumes.
+#[derive(Debug)]
+enum Handle {
+    /// A frame and the index of one of its scopes.
+    Scope(usize, usize),
+    Globals,
+    Instance(Value),
+}
+
+struct Adapter {
+    connection: Rc<RefCell<Connection>>,
+    program: PathBuf,
+    handles: Vec<Handle>,
+}
+
+impl Adapter {
+    fn handle(&mut self, handle: Handle) -> usize {
+        self.handles.push(handle);
+        self.handles.len()
+    }
+
+    fn variable(&mut self, name: &str, value: Value) -> Json {
+        let text = value.to_string();
+        let reference = match value.fields() {
+            Some(_) => self.handle(Handle::Instance(value)),
+            None => 0,
+        };
+        json!({ "name": name, "value": text, "variablesReference": reference })
+    }
+
+    fn request(
+        &mut self,
+        pause: &mut Pause<'_>,
+        command: &str,
+        arguments: &Json,
+    ) -> Result<Json, String> {
+        let frame = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+
+        match command {
+            "threads" => Ok(threads()),
+            "stackTrace" => {
+                let source = source(&self.program);
+                let frames: Vec<Json> = pause
+                    .stack()
+                    .enumerate()
+                    .map(|(id, frame)| {
+                        json!({
+                            "id": id,
+                            "name": frame.function,
+                            "source": source,
+                            "line": frame.line,
+                            "column": 1,
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+            }
+            "scopes" => {
+                let count = pause.scopes(frame).len();
+                let mut scopes = vec![];
+                for index in 0..count {
+                    let name = match index {
+                        0 => "Locals".to_string(),
+                        _ => format!("Enclosing scope {}", index),
+                    };
+                    let reference = self.handle(Handle::Scope(frame, index));
+                    scopes.push(json!({ "name": name, "variablesReference": reference, "expensive": false }));
+                }
+                let reference = self.handle(Handle::Globals);
+                scopes.push(json!({ "name": "Globals", "variablesReference": reference, "expensive": true }));
+                Ok(json!({ "scopes": scopes }))
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or(0) as usize;
+                let variables = match reference
+                    .checked_sub(1)
+                    .and_then(|index| self.handles.get(index))
+                {
+                    Some(&Handle::Scope(frame, index)) => pause
+                        .scopes(frame)
+                        .into_iter()
+                        .nth(index)
+                        .unwrap_or_default(),
+                    Some(Handle::Globals) => pause.globals(),
+                    Some(Handle::Instance(value)) => value.fields().unwrap_or_default(),
+                    None => return Err(format!("Unknown variablesReference {}.", reference)),
+                };
+                let variables: Vec<Json> = variables
+                    .into_iter()
+                    .map(|(name, value)| self.variable(&name, value))
+                    .collect();
+                Ok(json!({ "variables": variables }))
+            }
+            "evaluate" => {
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                let (result, diagnostics) = error::capture(|| pause.evaluate(frame, expression));
+                match result {
+                    Ok(value) => {
+                        let variable = self.variable("", value);
+                        Ok(json!({
+                            "result": variable["value"],
+                            "variablesReference": variable["variablesReference"],
+                        }))
+                    }
+                    Err(LoxError::RuntimeError(_, message)) => Err(message),
+                    Err(LoxError::AssertionFailed(_, message)) => {
+                        Err(format!("Assertion failed: {}", message))
+                    }
+                    Err(LoxError::CallDepthExceeded(_)) => Err("Stack overflow.".to_string()),
+                    Err(_) => Err(diagnostics
+                        .first()
+                        .map_or("Invalid expression.".to_string(), |d| d.message.clone())),
+                }
+            }
+            "setBreakpoints" => {
+                let lines = breakpoint_lines(arguments);
+                let old: Vec<usize> = pause.breakpoints().collect();
+                for line in old {
+                    pause.clear_breakpoint(line);
+                }
+                for &line in &lines {
+                    pause.set_breakpoint(line);
+                }
+                Ok(verified(&lines))
+            }
+            _ => Err(format!("Unsupported request '{}'.", command)),
+        }
+    }
+}
+
+fn source(program: &Path) -> Json {
+    let name = program
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    json!({ "name": name, "path": program.display().to_string() })
+}
+
+impl Frontend for Adapter {
+    fn paused(&mut self, pause: &mut Pause<'_>) -> Resume {
+        let reason = match pause.reason() {
+            Reason::Entry => "entry",
+            Reason::Step => "step",
+            Reason::Breakpoint => "breakpoint",
+        };
+        let body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if self.connection.borrow_mut().event("stopped", body).is_err() {
+            self.connection.borrow_mut().disconnected = true;
+            return Resume::Quit;
+        }
+
+        let resume = loop {
+            let request = match self.connection.borrow_mut().receive() {
+                Ok(Some(request)) => request,
+                // With no client left there is nobody to resume for.
+                Ok(None) | Err(_) => break None,
+            };
+            let command = request["command"].as_str().unwrap_or_default().to_string();
+
+            let resume = match command.as_str() {
+                "continue" => Some(Resume::Continue),
+                "next" => Some(Resume::StepOver),
+                "stepIn" => Some(Resume::StepIn),
+                "stepOut" => Some(Resume::StepOut),
+                "disconnect" => None,
+                _ => {
+                    let result = self.request(pause, &command, &request["arguments"]);
+                    if self
+                        .connection
+                        .borrow_mut()
+                        .respond(&request, result)
+                        .is_err()
+                    {
+                        break None;
+                    }
+                    continue;
+                }
+            };
+
+            let body = match resume {
+                Some(Resume::Continue) => json!({ "allThreadsContinued": true }),
+                _ => Json::Null,
+            };
+            if self
+                .connection
+                .borrow_mut()
+                .respond(&request, Ok(body))
+                .is_err()
+            {
+                break None;
+            }
+            break resume;
+        };
+
+        self.handles.clear();
+        resume.unwrap_or_else(|| {
+            self.connection.borrow_mut().disconnected = true;
+            Resume::Quit
+        })
+    }
+}
@@ -0,0 +1,492 @@
+//! A step debugger for the tree-walking interpreter.
+//!
+//! The interpreter asks the `Debugger` before every statement whether to
+//! stop. When it does, the `Frontend` gets a `Pause` to look at the call
+//! stack and variables, evaluate expressions and edit breakpoints, and says
+//! how to go on. Statements are the unit of stepping; blocks themselves
+//! never stop, only what is in them.
+
+mod terminal;
+
+pub use terminal::Terminal;
+
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+
+use crate::{
+    environment::Environment, error::LoxError, expr::Expr, interpreter::Interpreter,
+    object::Object, parser::Parser, scanner::Scanner, stmt::Stmt, token::Token,
+    token_type::TokenType,
+};
+
+/// Frame name for code outside of any function.
+const SCRIPT: &str = "<script>";
+
+pub trait Frontend {
+    /// Shows where the program stopped and decides how it goes on. The
+    /// program waits until this returns.
+    fn paused(&mut self, pause: &mut Pause<'_>) -> Resume;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Run until the next breakpoint.
+    Continue,
+    /// Stop at the next statement, inside a call if it makes one.
+    StepIn,
+    /// Stop at the next statement in this function or its callers.
+    StepOver,
+    /// Stop at the next statement after this function returns.
+    StepOut,
+    /// End the program, as `exit(0)` would.
+    Quit,
+}
+
+/// Why the program stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// Before the first statement.
+    Entry,
+    Step,
+    Breakpoint,
+}
+
+/// A call in progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub function: String,
+    /// The statement running in this frame; for callers, the one making the call.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Run,
+    In,
+    /// Stop at or above this depth.
+    Over(usize),
+    /// Stop above this depth.
+    Out(usize),
+}
+
+pub struct Debugger {
+    frontend: Box<dyn Frontend>,
+    breakpoints: BTreeSet<usize>,
+    step: Step,
+    started: bool,
+    frames: Vec<Frame>,
+    /// The environment each frame but the innermost made its call from.
+    callers: Vec<Rc<RefCell<Environment>>>,
+}
+
+impl Debugger {
+    /// Stops before the first statement, so that the frontend can set
+    /// breakpoints before anything runs.
+    pub fn new(frontend: impl Frontend + 'static) -> Self {
+        Self {
+            frontend: Box::new(frontend),
+            breakpoints: BTreeSet::new(),
+            step: Step::In,
+            started: false,
+            frames: vec![Frame {
+                function: SCRIPT.to_string(),
+                line: 0,
+            }],
+            callers: vec![],
+        }
+    }
+
+    /// Whether to stop before the first statement; only has an effect
+    /// before the program starts.
+    pub fn set_stop_on_entry(&mut self, stop: bool) {
+        self.step = if stop { Step::In } else { Step::Run };
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    pub(crate) fn push_frame(&mut self, function: &str, caller: Rc<RefCell<Environment>>) {
+        self.frames.push(Frame {
+            function: function.to_string(),
+            line: 0,
+        });
+        self.callers.push(caller);
+    }
+
+    pub(crate) fn pop_frame(&mut self) {
+        self.frames.pop();
+        self.callers.pop();
+    }
+
+    /// Called before `stmt` runs, with the debugger taken out of the
+    /// interpreter so that nothing evaluated during a pause stops again.
+    pub(crate) fn before(
+        &mut self,
+        interpreter: &mut Interpreter,
+        stmt: &Stmt,
+    ) -> Result<(), LoxError> {
+        let line = match (stmt, stmt.line()) {
+            (Stmt::Block(_), _) | (_, None) => return Ok(()),
+            (_, Some(line)) => line,
+        };
+
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("the script frame is never popped");
+        // A breakpoint hits when execution comes to its line from another
+        // one, not again for each statement on it.
+        let entered = frame.line != line;
+        frame.line = line;
+
+        let depth = self.frames.len();
+        let stepped = match self.step {
+            Step::Run => false,
+            Step::In => true,
+            Step::Over(over) => depth <= over,
+            Step::Out(out) => depth < out,
+        };
+        let hit = entered && self.breakpoints.contains(&line);
+        let started = std::mem::replace(&mut self.started, true);
+        if !stepped && !hit {
+            return Ok(());
+        }
+
+        let reason = match (hit, started) {
+            (true, _) => Reason::Breakpoint,
+            (false, false) => Reason::Entry,
+            (false, true) => Reason::Step,
+        };
+        let mut pause = Pause {
+            interpreter,
+            breakpoints: &mut self.breakpoints,
+            frames: &self.frames,
+            callers: &self.callers,
+            reason,
+        };
+        self.step = match self.frontend.paused(&mut pause) {
+            Resume::Continue => Step::Run,
+            Resume::StepIn => Step::In,
+            Resume::StepOver => Step::Over(depth),
+            Resume::StepOut => Step::Out(depth),
+            Resume::Quit => return Err(LoxError::Exit(0)),
+        };
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Debugger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Debugger")
+            .field("breakpoints", &self.breakpoints)
+            .field("step", &self.step)
+            .field("frames", &self.frames)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A value in the paused program.
+#[derive(Debug, Clone)]
+pub struct Value(Object);
+
+impl Value {
+    /// An instance's fields, sorted by name, or `None` if this is not one.
+    pub fn fields(&self) -> Option<Vec<(String, Value)>> {
+        match self.0 {
+            Object::Instance(ref instance) => Some(
+                instance
+                    .fields()
+                    .into_iter()
+                    .map(|(name, value)| (name, Value(value)))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The program as it is stopped before a statement. Frames are numbered
+/// from 0 for the innermost, as in `stack`.
+pub struct Pause<'a> {
+    interpreter: &'a mut Interpreter,
+    breakpoints: &'a mut BTreeSet<usize>,
+    frames: &'a [Frame],
+    callers: &'a [Rc<RefCell<Environment>>],
+    reason: Reason,
+}
+
+impl Pause<'_> {
+    pub fn reason(&self) -> Reason {
+        self.reason
+    }
+
+    /// The line of the statement about to run.
+    pub fn line(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.line)
+    }
+
+    /// The calls in progress, innermost first; the last is the script itself.
+    pub fn stack(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter().rev()
+    }
+
+    fn environment(&self, frame: usize) -> Option<Rc<RefCell<Environment>>> {
+        match frame {
+            0 => Some(self.interpreter.environment.clone()),
+            _ => {
+                let index = self.callers.len().checked_sub(frame)?;
+                Some(self.callers[index].clone())
+            }
+        }
+    }
+
+    /// The variables in each scope `frame` can see, innermost first and
+    /// sorted by name, up to but not including the globals. A method's
+    /// `this` is in a scope of its own.
+    pub fn scopes(&self, frame: usize) -> Vec<Vec<(String, Value)>> {
+        let mut scopes = vec![];
+
+        let Some(mut environment) = self.environment(frame) else {
+            return scopes;
+        };
+        while !Rc::ptr_eq(&environment, &self.interpreter.globals) {
+            let mut variables: Vec<_> = environment
+                .borrow()
+                .iter()
+                .map(|(name, value)| (name.to_string(), Value(value.clone())))
+                .collect();
+            variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+            scopes.push(variables);
+
+            let enclosing = environment.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => environment = enclosing,
+                None => break,
+            }
+        }
+
+        scopes
+    }
+
+    /// Each global with its value, sorted by name.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut globals: Vec<_> = self
+            .interpreter
+            .globals
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.to_string(), Value(value.clone())))
+            .collect();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        globals
+    }
+
+    /// Evaluates the expression `source` as if it were written in the
+    /// statement `frame` is running.
+    pub fn evaluate(&mut self, frame: usize, source: &str) -> Result<Value, LoxError> {
+        let tokens = Scanner::new(source).scan_tokens()?;
+        let expr = Parser::new(tokens).parse_expression()?;
+
+        let environment = self.environment(frame).ok_or_else(|| {
+            let token = Token::new(TokenType::Eof, String::new(), Object::Null, self.line());
+            LoxError::RuntimeError(token, format!("There is no frame {}.", frame))
+        })?;
+
+        // The resolver's scopes are long gone; find each name in the
+        // environments that are live now instead.
+        let mut resolved = vec![];
+        resolve_in(&expr, &environment, &mut resolved)?;
+        for (expr, depth) in &resolved {
+            self.interpreter.resolve(expr, *depth);
+        }
+
+        let paused = std::mem::replace(&mut self.interpreter.environment, environment);
+        let value = self.interpreter.evaluate(&expr);
+        self.interpreter.environment = paused;
+
+        for (expr, _) in &resolved {
+            self.interpreter.locals.remove(expr);
+        }
+        value.map(Value)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Returns false if there already was one on `line`.
+    pub fn set_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.insert(line)
+    }
+
+    /// Returns false if there was none on `line`.
+    pub fn clear_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.remove(&line)
+    }
+}
+
+/// Records how many scopes out from `environment` each local name in `expr`
+/// is defined. Names found nowhere but the globals are left to the globals.
+fn resolve_in(
+    expr: &Expr,
+    environment: &Rc<RefCell<Environment>>,
+    resolved: &mut Vec<(Expr, usize)>,
+) -> Result<(), LoxError> {
+    let resolve = |name: &str| {
+        let mut depth = 0;
+        let mut scope = environment.clone();
+        loop {
+            let enclosing = scope.borrow().enclosing.clone();
+            // The outermost environment is the globals.
+            let enclosing = enclosing?;
+            if scope.borrow().iter().any(|(defined, _)| defined == name) {
+                return Some(depth);
+            }
+            scope = enclosing;
+            depth += 1;
+        }
+    };
+
+    match expr {
+        Expr::Literal(_) => (),
+        Expr::Unary(unary) => resolve_in(&unary.right, environment, resolved)?,
+        Expr::Binary(binary) => {
+            resolve_in(&binary.left, environment, resolved)?;
+            resolve_in(&binary.right, environment, resolved)?;
+        }
+        Expr::Grouping(grouping) => resolve_in(&grouping.expression, environment, resolved)?,
+        Expr::Variable(variable) => {
+            if let Some(depth) = resolve(&variable.name.lexeme) {
+                resolved.push((expr.clone(), depth));
+            }
+        }
+        Expr::Assign(assign) => {
+            resolve_in(&assign.value, environment, resolved)?;
+            if let Some(depth) = resolve(&assign.name.lexeme) {
+                resolved.push((expr.clone(), depth));
+            }
+        }
+        Expr::Logical(logical) => {
+            resolve_in(&logical.left, environment, resolved)?;
+            resolve_in(&logical.right, environment, resolved)?;
+        }
+        Expr::Call(call) => {
+            resolve_in(&call.callee, environment, resolved)?;
+            for argument in &call.arguments {
+                resolve_in(argument, environment, resolved)?;
+            }
+        }
+        Expr::Get(get) => resolve_in(&get.object, environment, resolved)?,
+        Expr::Set(set) => {
+            resolve_in(&set.object, environment, resolved)?;
+            resolve_in(&set.value, environment, resolved)?;
+        }
+        Expr::This(_) => {
+            if let Some(depth) = resolve("this") {
+                resolved.push((expr.clone(), depth));
+            }
+        }
+        Expr::Super(sup) => match resolve("super") {
+            Some(depth) => resolved.push((expr.clone(), depth)),
+            // The interpreter counts on the resolver to have caught this.
+            None => {
+                return Err(LoxError::RuntimeError(
+                    sup.keyword.clone(),
+                    "Can't use 'super' here.".to_string(),
+                ))
+            }
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::run;
+
+    use super::*;
+
+    /// Follows a fixed list of commands, noting where it stopped and the
+    /// value of `watch` there.
+    struct Script {
+        commands: Vec<Resume>,
+        watch: &'static str,
+        stops: Rc<RefCell<Vec<(usize, String, String)>>>,
+    }
+
+    impl Frontend for Script {
+        fn paused(&mut self, pause: &mut Pause<'_>) -> Resume {
+            let function = pause.stack().next().unwrap().function.clone();
+            let value = pause
+                .evaluate(0, self.watch)
+                .map_or(String::new(), |value| value.to_string());
+            self.stops
+                .borrow_mut()
+                .push((pause.line(), function, value));
+            if pause.line() == 1 {
+                pause.set_breakpoint(3);
+            }
+            self.commands.pop().unwrap_or(Resume::Continue)
+        }
+    }
+
+    fn stops(commands: &[Resume]) -> Vec<(usize, String, String)> {
+        let source = "var a = 1;\n\
+                      fun f(n) {\n\
+                      \x20 var b = n + 1;\n\
+                      \x20 return b;\n\
+                      }\n\
+                      a = f(a);\n\
+                      a = f(a);\n";
+        let stops = Rc::new(RefCell::new(vec![]));
+        let script = Script {
+            commands: commands.iter().rev().copied().collect(),
+            watch: "a",
+            stops: stops.clone(),
+        };
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_debugger(Debugger::new(script));
+        run(source, &mut interpreter).unwrap();
+
+        stops.take()
+    }
+
+    #[test]
+    fn stops_at_breakpoints_and_steps() {
+        let stop = |line, function: &str, a: &str| (line, function.to_string(), a.to_string());
+
+        assert_eq!(
+            stops(&[Resume::Continue, Resume::Continue]),
+            vec![
+                stop(1, "<script>", ""),
+                stop(3, "f", "1"),
+                stop(3, "f", "2"),
+            ]
+        );
+        assert_eq!(
+            stops(&[
+                Resume::StepIn,
+                Resume::StepOver,
+                Resume::StepIn,
+                Resume::StepOut
+            ]),
+            vec![
+                stop(1, "<script>", ""),
+                stop(2, "<script>", "1"),
+                stop(6, "<script>", "1"),
+                stop(3, "f", "1"),
+                stop(7, "<script>", "2"),
+                stop(3, "f", "2"),
+            ]
+        );
+    }
+}
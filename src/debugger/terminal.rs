@@ -0,0 +1,170 @@
+use std::io::{self, Write};
+
+use crate::error::LoxError;
+
+use super::{Frontend, Pause, Resume};
+
+const HELP: &str = "\
+break LINE (b)     stop when execution reaches LINE
+delete LINE (d)    remove the breakpoint on LINE
+breakpoints        list breakpoints
+continue (c)       run to the next breakpoint
+step (s)           run one statement, stepping into calls
+next (n)           run one statement, stepping over calls
+finish (f)         run until the current function returns
+backtrace (bt)     show the call stack
+locals             show the variables in each enclosing scope
+globals            show the global variables
+print EXPR (p)     evaluate EXPR where the program is stopped
+list (l)           show the source around the current line
+quit (q)           end the program
+An empty line repeats the last step or continue.";
+
+/// A line-oriented frontend in the style of gdb, reading commands from stdin.
+#[derive(Debug)]
+pub struct Terminal {
+    lines: Vec<String>,
+    /// The last command that resumed the program.
+    last: Option<Resume>,
+}
+
+impl Terminal {
+    pub fn new(source: &str) -> Self {
+        Self {
+            lines: source.lines().map(str::to_string).collect(),
+            last: None,
+        }
+    }
+
+    fn source_line(&self, line: usize) -> &str {
+        line.checked_sub(1)
+            .and_then(|index| self.lines.get(index))
+            .map_or("", |text| text.trim())
+    }
+
+    /// Runs one command, returning how to resume if it resumes the program.
+    fn command(&mut self, pause: &mut Pause<'_>, line: &str) -> Option<Resume> {
+        let (command, argument) = match line.trim().split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line.trim(), ""),
+        };
+
+        let resume = match command {
+            "" => return self.last,
+            "c" | "continue" => Resume::Continue,
+            "s" | "step" => Resume::StepIn,
+            "n" | "next" => Resume::StepOver,
+            "f" | "finish" => Resume::StepOut,
+            "q" | "quit" => return Some(Resume::Quit),
+            "b" | "break" | "d" | "delete" => {
+                let Ok(line) = argument.parse::<usize>() else {
+                    println!("Expected a line number.");
+                    return None;
+                };
+                if command.starts_with('b') {
+                    pause.set_breakpoint(line);
+                    println!("Breakpoint at line {}.", line);
+                } else if !pause.clear_breakpoint(line) {
+                    println!("No breakpoint at line {}.", line);
+                }
+                return None;
+            }
+            "breakpoints" => {
+                for line in pause.breakpoints() {
+                    println!("line {}: {}", line, self.source_line(line));
+                }
+                return None;
+            }
+            "bt" | "backtrace" => {
+                for (depth, frame) in pause.stack().enumerate() {
+                    println!("#{} {} at line {}", depth, frame.function, frame.line);
+                }
+                return None;
+            }
+            "locals" => {
+                for (depth, scope) in pause.scopes(0).iter().enumerate() {
+                    println!("scope {}:", depth);
+                    for (name, value) in scope {
+                        println!("  {} = {}", name, value);
+                    }
+                }
+                return None;
+            }
+            "globals" => {
+                for (name, value) in pause.globals() {
+                    println!("{} = {}", name, value);
+                }
+                return None;
+            }
+            "p" | "print" => {
+                match pause.evaluate(0, argument) {
+                    Ok(value) => println!("{}", value),
+                    Err(LoxError::RuntimeError(_, message)) => println!("{}", message),
+                    Err(LoxError::AssertionFailed(_, message)) => {
+                        println!("Assertion failed: {}", message)
+                    }
+                    Err(LoxError::CallDepthExceeded(_)) => println!("Stack overflow."),
+                    // Static errors were already reported.
+                    Err(_) => (),
+                }
+                return None;
+            }
+            "l" | "list" => {
+                let current = pause.line();
+                let first = current.saturating_sub(3).max(1);
+                for line in first..=(current + 3).min(self.lines.len()) {
+                    let marker = if line == current { "->" } else { "  " };
+                    println!("{} {:>4} {}", marker, line, self.lines[line - 1]);
+                }
+                return None;
+            }
+            "h" | "help" => {
+                println!("{}", HELP);
+                return None;
+            }
+            _ => {
+                println!("Unknown command '{}'. Type help for a list.", command);
+                return None;
+            }
+        };
+
+        self.last = Some(resume);
+        Some(resume)
+    }
+}
+
+impl Frontend for Terminal {
+    fn paused(&mut self, pause: &mut Pause<'_>) -> Resume {
+        let line = pause.line();
+        let function = pause.stack().next().map(|frame| frame.function.clone());
+        match function {
+            Some(function) if pause.stack().nth(1).is_some() => {
+                println!("[line {} in {}] {}", line, function, self.source_line(line))
+            }
+            _ => println!("[line {}] {}", line, self.source_line(line)),
+        }
+
+        loop {
+            print!("(jlox) ");
+            let _ = io::stdout().flush();
+
+            let mut input = String::new();
+            match io::stdin().read_line(&mut input) {
+                Ok(0) | Err(_) => {
+                    // Out of commands: let the program finish on its own.
+                    println!();
+                    let lines: Vec<usize> = pause.breakpoints().collect();
+                    for line in lines {
+                        pause.clear_breakpoint(line);
+                    }
+                    return Resume::Continue;
+                }
+                Ok(_) => (),
+            }
+
+            if let Some(resume) = self.command(pause, &input) {
+                return resume;
+            }
+        }
+    }
+}
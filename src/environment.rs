@@ -49,6 +49,13 @@ impl Environment {
         ))
     }
 
+    /// Variables defined in this scope only, not in enclosing ones.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Object)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
     pub fn define(&mut self, name: String, value: Object) {
         self.values.insert(name, value);
     }
@@ -68,6 +75,11 @@ impl Environment {
     }
 
     pub fn assign_at(&mut self, distance: usize, name: &Token, value: Object) {
+        if distance == 0 {
+            self.values.insert(name.lexeme.to_string(), value);
+            return;
+        }
+
         self.ancestor(distance)
             .borrow_mut()
             .values
@@ -87,3 +99,20 @@ impl Environment {
         environment
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::token_type::TokenType;
+
+    use super::*;
+
+    #[test]
+    fn assigns_in_innermost_scope() {
+        let mut environment = Environment::new(None);
+        environment.define("a".to_string(), Object::Num(1f64));
+
+        let name = Token::new(TokenType::Identifier, "a".to_string(), Object::Null, 1);
+        environment.assign_at(0, &name, Object::Num(2f64));
+        assert_eq!(environment.get_at(0, "a"), Object::Num(2f64));
+    }
+}
@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+
 use crate::{object::Object, token::Token, token_type::TokenType};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -5,26 +7,90 @@ pub enum LoxError {
     ScanError,
     ParseError,
     ResolveError,
+    CompileError,
     RuntimeError(Token, String),
+    /// Raised by the `assert` native, with the line of the call.
+    AssertionFailed(usize, String),
     Return(Object),
+    /// Raised by the `exit` native; unwinds to the caller of `interpret`.
+    Exit(i32),
+    /// The program ran more statements than `Interpreter::set_step_limit`
+    /// allows.
+    StepLimitExceeded,
+    /// A call, made on the given line, nested deeper than
+    /// `Interpreter::set_call_depth_limit` allows.
+    CallDepthExceeded(usize),
+    /// The program ran longer than `Interpreter::set_time_limit` allows.
+    TimeLimitExceeded,
+    /// The program allocated more bytes for strings and instances than
+    /// `Interpreter::set_allocation_limit` allows.
+    AllocationLimitExceeded,
+    /// The program printed more than `Interpreter::set_output_limit` allows.
+    OutputLimitExceeded,
+}
+
+/// A static error as it was reported, for tools that show errors somewhere
+/// other than stderr.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    /// The token the error is at. Scan errors have none.
+    pub token: Option<Token>,
+    pub message: String,
+}
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<Diagnostic>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, collecting the static errors it reports instead of printing them.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    let outer = CAPTURED.with(|captured| captured.replace(Some(vec![])));
+    let result = f();
+    let diagnostics = CAPTURED.with(|captured| captured.replace(outer));
+
+    (result, diagnostics.unwrap_or_default())
 }
 
 pub fn lox_error_line(line: usize, message: &str) {
-    report(line, "", message);
+    report(line, None, "", message);
 }
 
-fn report(line: usize, where_: &str, message: &str) {
-    eprintln!("[line {line}] Error{where_}: {message}");
+fn report(line: usize, token: Option<&Token>, where_: &str, message: &str) {
+    let captured = CAPTURED.with(|captured| match *captured.borrow_mut() {
+        Some(ref mut diagnostics) => {
+            diagnostics.push(Diagnostic {
+                line,
+                token: token.cloned(),
+                message: message.to_string(),
+            });
+            true
+        }
+        None => false,
+    });
+
+    if !captured {
+        eprintln!("[line {line}] Error{where_}: {message}");
+    }
 }
 
 pub fn lox_error_token(token: &Token, message: &str) {
     if token.typ == TokenType::Eof {
-        report(token.line, " at end", message);
+        report(token.line, Some(token), " at end", message);
     } else {
-        report(token.line, &format!(" at '{}'", token.lexeme), message);
+        let where_ = format!(" at '{}'", token.lexeme);
+        report(token.line, Some(token), &where_, message);
     }
 }
 
 pub fn lox_runtime_error(token: &Token, message: &str) {
     eprintln!("{}\n[line {}]", message, token.line);
 }
+
+pub fn lox_stack_overflow(line: usize) {
+    eprintln!("Stack overflow.\n[line {}]", line);
+}
+
+pub fn lox_assertion_failed(line: usize, message: &str) {
+    eprintln!("Assertion failed: {}\n[line {}]", message, line);
+}
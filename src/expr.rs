@@ -54,10 +54,30 @@ impl Expr {
         }
     }
 
-    pub fn new_literal(value: Object) -> Self {
+    /// The line the expression starts on, as near as its tokens tell.
+    /// Literals the parser or optimizer made up have none.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Expr::Literal(expr) => expr.line,
+            Expr::Unary(expr) => Some(expr.operator.line),
+            Expr::Binary(expr) => expr.left.line().or(Some(expr.operator.line)),
+            Expr::Grouping(expr) => expr.expression.line(),
+            Expr::Variable(expr) => Some(expr.name.line),
+            Expr::Assign(expr) => Some(expr.name.line),
+            Expr::Logical(expr) => expr.left.line().or(Some(expr.operator.line)),
+            Expr::Call(expr) => expr.callee.line().or(Some(expr.paren.line)),
+            Expr::Get(expr) => expr.object.line().or(Some(expr.name.line)),
+            Expr::Set(expr) => expr.object.line().or(Some(expr.name.line)),
+            Expr::This(expr) => Some(expr.keyword.line),
+            Expr::Super(expr) => Some(expr.keyword.line),
+        }
+    }
+
+    pub fn new_literal(value: Object, line: Option<usize>) -> Self {
         Self::Literal(ExprLiteral {
             id: Uuid::new_v4(),
             value,
+            line,
         })
     }
 
@@ -196,6 +216,8 @@ impl std::hash::Hash for Expr {
 pub struct ExprLiteral {
     id: Uuid,
     pub value: Object,
+    /// Where the literal was read from, if it was.
+    pub line: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
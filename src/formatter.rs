@@ -0,0 +1,496 @@
+//! Canonical source formatting for `jlox fmt`.
+//!
+//! The formatter walks the syntax tree and the token stream side by side.
+//! Every token of the source is printed exactly once and in order, so literals
+//! keep their spelling and each comment goes back in front of the token it
+//! preceded. `for` loops arrive desugared into `while`; a `for` token at the
+//! cursor is how the formatter recognizes one and prints it back.
+
+use std::rc::Rc;
+
+use crate::{
+    error::LoxError,
+    expr::*,
+    parser::Parser,
+    scanner::{Comment, Scanner},
+    stmt::*,
+    token::Token,
+    token_type::TokenType::{
+        self, Class, Comma, Dot, Else, Eof, Equal, For, Fun, Identifier, If, LeftBrace, LeftParen,
+        Less, Print, Return, RightBrace, RightParen, Semicolon, Super, This, Var, While,
+    },
+};
+
+const INDENT: &str = "  ";
+
+/// Formats a whole program. Scan and parse errors are reported as usual.
+pub fn format(source: &str) -> Result<String, LoxError> {
+    let (tokens, comments) = Scanner::new(source).scan_tokens_and_comments()?;
+    let statements = Parser::new(tokens.clone()).parse()?;
+
+    let mut formatter = Formatter::new(&tokens, &comments);
+    if let Some(shebang) = source.lines().next().filter(|line| line.starts_with("#!")) {
+        formatter.lines.push(shebang.to_string());
+        formatter.last_line = 1;
+    }
+
+    for stmt in &statements {
+        formatter.start_line();
+        formatter.stmt(stmt);
+    }
+
+    Ok(formatter.finish())
+}
+
+#[derive(Debug)]
+struct Formatter<'a> {
+    tokens: &'a [Token],
+    comments: &'a [Comment],
+    current: usize,
+    next_comment: usize,
+    lines: Vec<String>,
+    // The output line being built; indentation is added by the first write.
+    line: String,
+    indent: usize,
+    // Set when a comment broke a statement over more than one line.
+    continuation: bool,
+    // Source line of the last token or comment printed.
+    last_line: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(tokens: &'a [Token], comments: &'a [Comment]) -> Self {
+        Self {
+            tokens,
+            comments,
+            current: 0,
+            next_comment: 0,
+            lines: vec![],
+            line: String::new(),
+            indent: 0,
+            continuation: false,
+            last_line: 0,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.finish_line();
+        self.continuation = false;
+        // Comments after the last statement.
+        self.flush_comments();
+        debug_assert_eq!(self.peek().typ, Eof, "formatter left tokens behind");
+
+        let mut output = self.lines.join("\n");
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        stmt.accept(self)
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn peek(&self) -> &'a Token {
+        &self.tokens[self.current]
+    }
+
+    fn check(&self, typ: TokenType) -> bool {
+        self.peek().typ == typ
+    }
+
+    /// Prints the token at the cursor, after any comments that preceded it.
+    fn token(&mut self, typ: TokenType) {
+        self.flush_comments();
+
+        let token = self.peek();
+        debug_assert_eq!(token.typ, typ, "formatter out of step at {}", token);
+
+        self.write(&token.lexeme);
+        self.last_line = token.line;
+        self.current += 1;
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.line.is_empty() {
+            let depth = self.indent + self.continuation as usize;
+            self.line.push_str(&INDENT.repeat(depth));
+        }
+        self.line.push_str(text);
+    }
+
+    fn finish_line(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.lines.push(line.trim_end().to_string());
+        }
+    }
+
+    /// Starts a statement on a line of its own, keeping one blank line where
+    /// the source had any.
+    fn start_line(&mut self) {
+        self.finish_line();
+        self.continuation = false;
+        self.flush_comments();
+        self.blank_line_before(self.peek().line);
+    }
+
+    fn blank_line_before(&mut self, line: usize) {
+        let after_open = self
+            .lines
+            .last()
+            .is_none_or(|last| last.is_empty() || last.ends_with('{'));
+
+        if line > self.last_line + 1 && !after_open {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn comment_pending(&self) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .is_some_and(|comment| comment.before_token <= self.current)
+    }
+
+    fn flush_comments(&mut self) {
+        while self.comment_pending() {
+            let comment = &self.comments[self.next_comment];
+            self.next_comment += 1;
+            let mid_line = !self.line.is_empty();
+
+            if comment.trailing {
+                match self.lines.last_mut() {
+                    Some(last) if !mid_line => {
+                        last.push(' ');
+                        last.push_str(&comment.text);
+                    }
+                    _ => {
+                        self.line.truncate(self.line.trim_end().len());
+                        self.write(" ");
+                        self.write(&comment.text);
+                        self.finish_line();
+                    }
+                }
+            } else {
+                self.finish_line();
+                self.blank_line_before(comment.line);
+                self.write(&comment.text);
+                self.finish_line();
+            }
+
+            self.last_line = comment.line;
+            if mid_line {
+                self.continuation = true;
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Stmt]) {
+        self.token(LeftBrace);
+
+        if statements.is_empty() && !self.comment_pending() {
+            self.token(RightBrace);
+            return;
+        }
+
+        self.indent += 1;
+        for stmt in statements {
+            self.start_line();
+            self.stmt(stmt);
+        }
+
+        self.finish_line();
+        self.continuation = false;
+        // Comments before the `}` stay inside the block.
+        self.flush_comments();
+        self.indent -= 1;
+        self.token(RightBrace);
+    }
+
+    // The body of an `if`, `while` or `for`: a block opens on the same line,
+    // and so does a single statement.
+    fn body(&mut self, stmt: &Stmt) {
+        self.write(" ");
+        self.stmt(stmt);
+    }
+
+    fn binary(&mut self, left: &Expr, operator: &Token, right: &Expr) {
+        self.expr(left);
+        self.write(" ");
+        self.token(operator.typ);
+        self.write(" ");
+        self.expr(right);
+    }
+
+    fn for_loop(&mut self, initializer: Option<&Stmt>, stmt: &StmtWhile) {
+        self.token(For);
+        self.write(" ");
+        self.token(LeftParen);
+
+        match initializer {
+            Some(initializer) => self.stmt(initializer),
+            None => self.token(Semicolon),
+        }
+
+        // Without a condition the parser put in a literal `true`.
+        if !self.check(Semicolon) {
+            self.write(" ");
+            self.expr(&stmt.condition);
+        }
+        self.token(Semicolon);
+
+        // The parser appends the increment to the body.
+        let body = if self.check(RightParen) {
+            &*stmt.body
+        } else {
+            let Stmt::Block(ref block) = *stmt.body else {
+                unreachable!("for loop body without its increment");
+            };
+            let [body, Stmt::Expression(increment)] = block.statements.as_slice() else {
+                unreachable!("for loop body without its increment");
+            };
+            self.write(" ");
+            self.expr(&increment.expression);
+            body
+        };
+        self.token(RightParen);
+
+        self.body(body);
+    }
+}
+
+impl ExprVisitor<()> for Formatter<'_> {
+    fn visit_literal_expr(&mut self, _expr: &ExprLiteral) {
+        // Printed as spelled in the source.
+        self.token(self.peek().typ);
+    }
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) {
+        self.token(expr.operator.typ);
+        self.expr(&expr.right);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) {
+        self.binary(&expr.left, &expr.operator, &expr.right);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) {
+        self.token(LeftParen);
+        self.expr(&expr.expression);
+        self.token(RightParen);
+    }
+
+    fn visit_variable_expr(&mut self, _expr: &ExprVariable) {
+        self.token(Identifier);
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) {
+        self.token(Identifier);
+        self.write(" ");
+        self.token(Equal);
+        self.write(" ");
+        self.expr(&expr.value);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) {
+        self.binary(&expr.left, &expr.operator, &expr.right);
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) {
+        self.expr(&expr.callee);
+        self.token(LeftParen);
+        for (i, argument) in expr.arguments.iter().enumerate() {
+            if i > 0 {
+                self.token(Comma);
+                self.write(" ");
+            }
+            self.expr(argument);
+        }
+        self.token(RightParen);
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) {
+        self.expr(&expr.object);
+        self.token(Dot);
+        self.token(Identifier);
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) {
+        self.expr(&expr.object);
+        self.token(Dot);
+        self.token(Identifier);
+        self.write(" ");
+        self.token(Equal);
+        self.write(" ");
+        self.expr(&expr.value);
+    }
+
+    fn visit_this_expr(&mut self, _expr: &ExprThis) {
+        self.token(This);
+    }
+
+    fn visit_super_expr(&mut self, _expr: &ExprSuper) {
+        self.token(Super);
+        self.token(Dot);
+        self.token(Identifier);
+    }
+}
+
+impl StmtVisitor<()> for Formatter<'_> {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) {
+        self.expr(&stmt.expression);
+        self.token(Semicolon);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) {
+        self.token(Print);
+        self.write(" ");
+        self.expr(&stmt.expression);
+        self.token(Semicolon);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) {
+        self.token(Var);
+        self.write(" ");
+        self.token(Identifier);
+        if let Some(ref initializer) = stmt.initializer {
+            self.write(" ");
+            self.token(Equal);
+            self.write(" ");
+            self.expr(initializer);
+        }
+        self.token(Semicolon);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) {
+        if self.check(For) {
+            if let [initializer, Stmt::While(ref stmt)] = stmt.statements.as_slice() {
+                return self.for_loop(Some(initializer), stmt);
+            }
+        }
+        self.block(&stmt.statements);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) {
+        self.token(If);
+        self.write(" ");
+        self.token(LeftParen);
+        self.expr(&stmt.condition);
+        self.token(RightParen);
+        self.body(&stmt.then_branch);
+
+        if let Some(ref else_branch) = stmt.else_branch {
+            // `} else {`, but a single-statement branch puts `else` on its own line.
+            self.flush_comments();
+            if self.line.ends_with('}') {
+                self.write(" ");
+            } else {
+                self.finish_line();
+                self.continuation = false;
+            }
+            self.token(Else);
+            self.body(else_branch);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) {
+        if self.check(For) {
+            return self.for_loop(None, stmt);
+        }
+
+        self.token(While);
+        self.write(" ");
+        self.token(LeftParen);
+        self.expr(&stmt.condition);
+        self.token(RightParen);
+        self.body(&stmt.body);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) {
+        // Methods have no `fun`.
+        if self.check(Fun) {
+            self.token(Fun);
+            self.write(" ");
+        }
+        self.token(Identifier);
+
+        self.token(LeftParen);
+        for i in 0..stmt.params.len() {
+            if i > 0 {
+                self.token(Comma);
+                self.write(" ");
+            }
+            self.token(Identifier);
+        }
+        self.token(RightParen);
+
+        self.write(" ");
+        self.block(&stmt.body);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) {
+        self.token(Return);
+        if let Some(ref value) = stmt.value {
+            self.write(" ");
+            self.expr(value);
+        }
+        self.token(Semicolon);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) {
+        self.token(Class);
+        self.write(" ");
+        self.token(Identifier);
+
+        if let Some(ref superclass) = stmt.superclass {
+            self.write(" ");
+            self.token(Less);
+            self.write(" ");
+            self.expr(superclass);
+        }
+
+        self.write(" ");
+        self.block(&stmt.methods);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_to_canonical_layout() {
+        let source = "fun  f(a,b){\n\tif(a>b)return a;else{return -b ;}}\n\n\n\
+                      class B<A{m(){super.m( 1.50 ,\"x\" );}}\n\
+                      for(var i=0;i<3;i=i+1)print i;for(;;){}";
+
+        assert_eq!(
+            format(source).unwrap(),
+            "fun f(a, b) {\n  if (a > b) return a;\n  else {\n    return -b;\n  }\n}\n\n\
+             class B < A {\n  m() {\n    super.m(1.50, \"x\");\n  }\n}\n\
+             for (var i = 0; i < 3; i = i + 1) print i;\nfor (;;) {}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comments_in_place() {
+        let source = "#!/usr/bin/env jlox\n// Leading.\nvar a = 1; // Trailing.\n\n\
+                      {\n  // Inside.\n  print a;\n  // Before the brace.\n}\n\
+                      if (a) { // After the brace.\n} else print a;\n// At the end.\n";
+
+        assert_eq!(format(source).unwrap(), source);
+    }
+
+    #[test]
+    fn breaks_lines_at_comments_inside_statements() {
+        let source = "var a = 1 + // One.\n2;\nprint a;";
+        let formatted = "var a = 1 + // One.\n  2;\nprint a;\n";
+
+        assert_eq!(format(source).unwrap(), formatted);
+        assert_eq!(format(formatted).unwrap(), formatted);
+    }
+}
@@ -1,21 +1,63 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc, time::SystemTime};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Write},
+    rc::Rc,
+    time::{Duration, Instant, SystemTime},
+};
 
 use crate::{
+    closure_compiler::{ClosureCompiler, CompiledBlock},
+    coverage::Coverage,
+    debugger::Debugger,
     environment::Environment,
     error::{self, LoxError},
     expr::*,
+    limits::Limits,
     lox_callable::*,
     object::Object,
+    profiler::{self, Profiler},
     stmt::*,
     token::Token,
     token_type::TokenType,
+    tracer::Tracer,
 };
 
+/// How `Interpreter::interpret` runs a resolved program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionMode {
+    /// Walk the AST through `Stmt::accept`/`Expr::accept` on every evaluation.
+    #[default]
+    TreeWalk,
+    /// Compile the program once into a tree of Rust closures, then run those.
+    Closure,
+}
+
+/// Where `print` writes.
+struct Output(Box<dyn Write>);
+
+impl std::fmt::Debug for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Output")
+    }
+}
+
 #[derive(Debug)]
 pub struct Interpreter {
-    globals: Rc<RefCell<Environment>>,
-    environment: Rc<RefCell<Environment>>,
-    locals: HashMap<Expr, usize>,
+    pub(crate) globals: Rc<RefCell<Environment>>,
+    pub(crate) environment: Rc<RefCell<Environment>>,
+    pub(crate) locals: HashMap<Expr, usize>,
+    mode: ExecutionMode,
+    args: Vec<String>,
+    /// The line of the call being made, for natives that report where they
+    /// were called from.
+    pub(crate) call_line: usize,
+    pub(crate) limits: Limits,
+    debugger: Option<Box<Debugger>>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    tracer: Option<Box<Tracer>>,
+    output: Output,
 }
 
 impl Interpreter {
@@ -34,34 +76,249 @@ impl Interpreter {
                     .as_secs_f64();
                 Ok(Object::Num(time))
             }
-            NativeFunction::new(clock, 0)
+            NativeFunction::new("clock", clock, 0)
         };
 
-        globals.define(
-            "clock".to_string(),
-            Object::Callable(CallableKind::Native(fn_clock)),
-        );
+        // The script's path (or `-e`) followed by its arguments.
+        let fn_argc = {
+            fn argc(
+                interpreter: &mut Interpreter,
+                _arguments: &[Object],
+            ) -> Result<Object, LoxError> {
+                Ok(Object::Num(interpreter.args.len() as f64))
+            }
+            NativeFunction::new("argc", argc, 0)
+        };
+
+        // `nil` for anything but the index of an argument.
+        let fn_argv = {
+            fn argv(
+                interpreter: &mut Interpreter,
+                arguments: &[Object],
+            ) -> Result<Object, LoxError> {
+                let arg = match arguments[0] {
+                    Object::Num(index) if index >= 0.0 && index.fract() == 0.0 => {
+                        interpreter.args.get(index as usize)
+                    }
+                    _ => None,
+                };
+                Ok(arg.map_or(Object::Null, |arg| Object::Str(arg.clone())))
+            }
+            NativeFunction::new("argv", argv, 1)
+        };
+
+        // Like Python's `sys.exit`: `nil` is success, a number is the status,
+        // anything else is printed and fails.
+        let fn_exit = {
+            fn exit(
+                _interpreter: &mut Interpreter,
+                arguments: &[Object],
+            ) -> Result<Object, LoxError> {
+                let code = match arguments[0] {
+                    Object::Null => 0,
+                    Object::Num(code) => code as i32,
+                    ref value => {
+                        eprintln!("{}", value);
+                        1
+                    }
+                };
+                Err(LoxError::Exit(code))
+            }
+            NativeFunction::new("exit", exit, 1)
+        };
+
+        // Fails with `message` unless `condition` is truthy.
+        let fn_assert = {
+            fn assert(
+                interpreter: &mut Interpreter,
+                arguments: &[Object],
+            ) -> Result<Object, LoxError> {
+                if arguments[0].is_truthy() {
+                    return Ok(Object::Null);
+                }
+                Err(LoxError::AssertionFailed(
+                    interpreter.call_line,
+                    arguments[1].to_string(),
+                ))
+            }
+            NativeFunction::new("assert", assert, 2)
+        };
+
+        for (name, function) in [
+            ("clock", fn_clock),
+            ("argc", fn_argc),
+            ("argv", fn_argv),
+            ("exit", fn_exit),
+            ("assert", fn_assert),
+        ] {
+            globals.define(
+                name.to_string(),
+                Object::Callable(CallableKind::Native(function)),
+            );
+        }
 
         let globals = Rc::new(RefCell::new(globals));
         Self {
             environment: globals.clone(),
             globals,
             locals: HashMap::new(),
+            mode: ExecutionMode::default(),
+            args: vec![],
+            call_line: 0,
+            limits: Limits::default(),
+            debugger: None,
+            profiler: None,
+            coverage: None,
+            tracer: None,
+            output: Output(Box::new(io::stdout())),
         }
     }
 
-    pub fn interpret(&mut self, statements: &Vec<Stmt>) -> Result<(), LoxError> {
-        for statement in statements {
-            match self.execute(statement) {
-                Err(LoxError::RuntimeError(token, message)) => {
-                    error::lox_runtime_error(&token, &message);
-                    return Err(LoxError::RuntimeError(token, message));
-                }
-                Err(error) => return Err(error),
-                _ => (),
+    /// Sets what the `argc` and `argv` natives see.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn execution_mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) {
+        self.mode = mode;
+    }
+
+    /// Sends what `print` writes to `output` instead of stdout, for hosts
+    /// that use stdout themselves.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Output(Box::new(output));
+    }
+
+    /// Pauses the program under `debugger`. Debugging always walks the AST,
+    /// whatever the execution mode.
+    pub fn set_debugger(&mut self, debugger: Debugger) {
+        self.debugger = Some(Box::new(debugger));
+    }
+
+    /// Times every call from now on, in either execution mode.
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    /// Stops the program with `LoxError::StepLimitExceeded` once it has run
+    /// `steps` more statements. Counting them always walks the AST.
+    pub fn set_step_limit(&mut self, steps: u64) {
+        self.limits.steps_left = Some(steps);
+    }
+
+    /// Stops the program with `LoxError::CallDepthExceeded` when a call would
+    /// nest more than `depth` calls deep, rather than let it overflow the
+    /// stack. Defaults to `limits::DEFAULT_CALL_DEPTH`, which needs a thread
+    /// with a `limits::STACK_SIZE` stack; see `limits::with_stack`.
+    pub fn set_call_depth_limit(&mut self, depth: usize) {
+        self.limits.max_call_depth = depth;
+    }
+
+    /// Stops the program with `LoxError::TimeLimitExceeded` at the first
+    /// statement that starts once `duration` has passed. Like the step
+    /// limit, this always walks the AST.
+    pub fn set_time_limit(&mut self, duration: Duration) {
+        self.limits.deadline = Some(Instant::now() + duration);
+    }
+
+    /// Stops the program with `LoxError::AllocationLimitExceeded` before it
+    /// allocates more than `bytes` more bytes. Concatenation is charged the
+    /// length of the new string, and an instance the size of the instance
+    /// itself, not counting its fields.
+    pub fn set_allocation_limit(&mut self, bytes: u64) {
+        self.limits.allocation_left = Some(bytes);
+    }
+
+    /// Stops the program with `LoxError::OutputLimitExceeded` at the first
+    /// `print` that would take what it has printed past `bytes` more bytes.
+    pub fn set_output_limit(&mut self, bytes: u64) {
+        self.limits.output_left = Some(bytes);
+    }
+
+    /// Records which lines and branches run from now on. Like debugging,
+    /// this always walks the AST.
+    pub fn set_coverage(&mut self, coverage: Coverage) {
+        self.coverage = Some(coverage);
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Logs each statement, definition, assignment and call as it runs.
+    /// Tracing always walks the AST.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Whether something watches each statement of the program as written,
+    /// so it has to run unoptimized and on the tree-walker.
+    pub(crate) fn watches_statements(&self) -> bool {
+        self.debugger.is_some() || self.coverage.is_some() || self.tracer.is_some()
+    }
+
+    /// Names currently defined in the global scope, natives included.
+    pub fn global_names(&self) -> Vec<String> {
+        self.globals
+            .borrow()
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Each global with its value as `print` would show it, sorted by name.
+    pub fn global_values(&self) -> Vec<(String, String)> {
+        let mut values: Vec<_> = self
+            .globals
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        values.sort();
+        values
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), LoxError> {
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.register(statements);
+        }
+        // These all watch each statement as it runs.
+        let mode = if self.watches_statements() || self.limits.watch_statements() {
+            ExecutionMode::TreeWalk
+        } else {
+            self.mode
+        };
+        let result = self.profiled(profiler::SCRIPT, |interpreter| match mode {
+            ExecutionMode::TreeWalk => statements
+                .iter()
+                .try_for_each(|statement| interpreter.execute(statement)),
+            ExecutionMode::Closure => {
+                let program = ClosureCompiler::new(&interpreter.locals).compile(statements);
+                program
+                    .iter()
+                    .try_for_each(|statement| statement(interpreter))
             }
+        });
+
+        match result {
+            Err(LoxError::RuntimeError(ref token, ref message)) => {
+                error::lox_runtime_error(token, message)
+            }
+            Err(LoxError::AssertionFailed(line, ref message)) => {
+                error::lox_assertion_failed(line, message)
+            }
+            Err(LoxError::CallDepthExceeded(line)) => error::lox_stack_overflow(line),
+            _ => (),
         }
-        Ok(())
+        result
     }
 
     pub fn resolve(&mut self, expr: &Expr, depth: usize) {
@@ -87,14 +344,89 @@ impl Interpreter {
         Ok(())
     }
 
-    fn evaluate(&mut self, expr: &Expr) -> Result<Object, LoxError> {
+    pub(crate) fn execute_compiled_block(
+        &mut self,
+        statements: &CompiledBlock,
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), LoxError> {
+        let previous = self.environment.clone();
+        self.environment = environment;
+
+        for statement in statements.iter() {
+            if let Err(e) = statement(self) {
+                self.environment = previous;
+                return Err(e);
+            }
+        }
+        self.environment = previous;
+
+        Ok(())
+    }
+
+    pub(crate) fn evaluate(&mut self, expr: &Expr) -> Result<Object, LoxError> {
         expr.accept(self)
     }
 
     fn execute(&mut self, stmt: &Stmt) -> Result<(), LoxError> {
+        self.limits.step()?;
+        if let Some(mut debugger) = self.debugger.take() {
+            let result = debugger.before(self, stmt);
+            self.debugger = Some(debugger);
+            result?;
+        }
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.hit(stmt);
+        }
+        if let Some(ref tracer) = self.tracer {
+            tracer.statement(stmt);
+        }
         stmt.accept(self)
     }
 
+    pub(crate) fn print(&mut self, value: &Object) -> Result<(), LoxError> {
+        let line = format!("{}\n", value);
+        self.limits.write(line.len())?;
+        self.output
+            .0
+            .write_all(line.as_bytes())
+            .expect("failed to print");
+        Ok(())
+    }
+
+    /// Tells the debugger, if there is one, that `function` was called.
+    pub(crate) fn enter_function(&mut self, function: &str) {
+        if let Some(ref mut debugger) = self.debugger {
+            debugger.push_frame(function, self.environment.clone());
+        }
+    }
+
+    pub(crate) fn leave_function(&mut self) {
+        if let Some(ref mut debugger) = self.debugger {
+            debugger.pop_frame();
+        }
+    }
+
+    /// Runs `call` as a call to `name` for the profiler, if there is one.
+    pub(crate) fn profiled<T>(&mut self, name: &str, call: impl FnOnce(&mut Self) -> T) -> T {
+        let Some(ref mut profiler) = self.profiler else {
+            return call(self);
+        };
+        profiler.enter(name);
+        let result = call(self);
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.leave();
+        }
+        result
+    }
+
+    /// Tells the coverage, if any, whether the branch point at `token` took
+    /// its second branch.
+    fn cover_branch(&mut self, token: &Token, second: bool) {
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.branch(token, second as usize);
+        }
+    }
+
     fn look_up_variable(&self, name: &Token, expr: &Expr) -> Result<Object, LoxError> {
         if let Some(distance) = self.locals.get(expr) {
             Ok(self
@@ -156,14 +488,15 @@ impl ExprVisitor<Result<Object, LoxError>> for Interpreter {
             TokenType::BangEqual => Ok(Object::Bool(left != right)),
             TokenType::EqualEqual => Ok(Object::Bool(left == right)),
             TokenType::Minus => {
-                check_number_operand(&expr.operator, &right)?;
+                check_number_operands(&expr.operator, &left, &right)?;
                 Ok(left - right)
             }
             TokenType::Plus => {
                 if left.is_num() && right.is_num() {
                     return Ok(left + right);
                 }
-                if left.is_str() && right.is_str() {
+                if let (Object::Str(a), Object::Str(b)) = (&left, &right) {
+                    self.limits.allocate(a.len() + b.len())?;
                     return Ok(left + right);
                 }
                 Err(LoxError::RuntimeError(
@@ -205,6 +538,9 @@ impl ExprVisitor<Result<Object, LoxError>> for Interpreter {
                 .borrow_mut()
                 .assign(&expr.name, value.clone())?
         }
+        if let Some(ref tracer) = self.tracer {
+            tracer.assign(&expr.name, &value);
+        }
 
         Ok(value)
     }
@@ -212,11 +548,13 @@ impl ExprVisitor<Result<Object, LoxError>> for Interpreter {
     fn visit_logical_expr(&mut self, expr: &ExprLogical) -> Result<Object, LoxError> {
         let left = self.evaluate(&expr.left)?;
 
-        if expr.operator.typ == TokenType::Or {
-            if left.is_truthy() {
-                return Ok(left);
-            }
-        } else if !left.is_truthy() {
+        let short_circuits = if expr.operator.typ == TokenType::Or {
+            left.is_truthy()
+        } else {
+            !left.is_truthy()
+        };
+        self.cover_branch(&expr.operator, short_circuits);
+        if short_circuits {
             return Ok(left);
         }
 
@@ -252,7 +590,15 @@ impl ExprVisitor<Result<Object, LoxError>> for Interpreter {
             ));
         }
 
-        function.call(self, &arguments)
+        self.call_line = expr.paren.line;
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.call(function.name(), &arguments);
+        }
+        let result = function.call(self, &arguments);
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.ret(function.name(), &result);
+        }
+        result
     }
 
     fn visit_get_expr(&mut self, expr: &ExprGet) -> Result<Object, LoxError> {
@@ -330,7 +676,7 @@ impl ExprVisitor<Result<Object, LoxError>> for Interpreter {
     }
 }
 
-fn check_number_operand(operator: &Token, operand: &Object) -> Result<(), LoxError> {
+pub(crate) fn check_number_operand(operator: &Token, operand: &Object) -> Result<(), LoxError> {
     if operand.is_num() {
         return Ok(());
     }
@@ -341,7 +687,11 @@ fn check_number_operand(operator: &Token, operand: &Object) -> Result<(), LoxErr
     ))
 }
 
-fn check_number_operands(operator: &Token, left: &Object, right: &Object) -> Result<(), LoxError> {
+pub(crate) fn check_number_operands(
+    operator: &Token,
+    left: &Object,
+    right: &Object,
+) -> Result<(), LoxError> {
     if left.is_num() && right.is_num() {
         return Ok(());
     }
@@ -361,9 +711,7 @@ impl StmtVisitor<Result<(), LoxError>> for Interpreter {
 
     fn visit_print_stmt(&mut self, stmt: &StmtPrint) -> Result<(), LoxError> {
         let value = self.evaluate(&stmt.expression)?;
-        println!("{}", value);
-
-        Ok(())
+        self.print(&value)
     }
 
     fn visit_var_stmt(&mut self, stmt: &StmtVar) -> Result<(), LoxError> {
@@ -371,6 +719,9 @@ impl StmtVisitor<Result<(), LoxError>> for Interpreter {
         if let Some(ref initializer) = stmt.initializer {
             value = self.evaluate(initializer)?;
         }
+        if let Some(ref tracer) = self.tracer {
+            tracer.define(&stmt.name, &value);
+        }
 
         self.environment
             .as_ref()
@@ -390,7 +741,9 @@ impl StmtVisitor<Result<(), LoxError>> for Interpreter {
     }
 
     fn visit_if_stmt(&mut self, stmt: &StmtIf) -> Result<(), LoxError> {
-        if self.evaluate(&stmt.condition)?.is_truthy() {
+        let condition = self.evaluate(&stmt.condition)?.is_truthy();
+        self.cover_branch(&stmt.keyword, !condition);
+        if condition {
             self.execute(&stmt.then_branch)?;
         } else if let Some(ref else_branch) = stmt.else_branch {
             self.execute(else_branch)?;
@@ -400,14 +753,19 @@ impl StmtVisitor<Result<(), LoxError>> for Interpreter {
     }
 
     fn visit_while_stmt(&mut self, stmt: &StmtWhile) -> Result<(), LoxError> {
-        while self.evaluate(&stmt.condition)?.is_truthy() {
+        loop {
+            let condition = self.evaluate(&stmt.condition)?.is_truthy();
+            self.cover_branch(&stmt.keyword, !condition);
+            if !condition {
+                break;
+            }
             self.execute(&stmt.body)?;
         }
 
         Ok(())
     }
 
-    fn visit_function_stmt(&mut self, stmt: &StmtFunction) -> Result<(), LoxError> {
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) -> Result<(), LoxError> {
         let function = LoxFunction::new(stmt.clone(), self.environment.clone(), false);
         self.environment.as_ref().borrow_mut().define(
             stmt.name.lexeme.clone(),
@@ -579,4 +937,67 @@ mod test {
             Ok(Object::Num(-3f64))
         );
     }
+
+    #[test]
+    fn stops_at_resource_limits() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_time_limit(Duration::from_millis(10));
+        assert_eq!(
+            crate::run("while (true) {}", &mut interpreter),
+            Err(LoxError::TimeLimitExceeded)
+        );
+
+        let doubling = "var s = \"a\"; for (var i = 0; i < 60; i = i + 1) s = s + s;";
+        for mode in [ExecutionMode::TreeWalk, ExecutionMode::Closure] {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_execution_mode(mode);
+            interpreter.set_allocation_limit(100);
+            assert_eq!(
+                crate::run(doubling, &mut interpreter),
+                Err(LoxError::AllocationLimitExceeded)
+            );
+        }
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_allocation_limit(1000);
+        assert_eq!(
+            crate::run("class A {} while (true) A();", &mut interpreter),
+            Err(LoxError::AllocationLimitExceeded)
+        );
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(io::sink());
+        interpreter.set_output_limit(8);
+        assert_eq!(
+            crate::run("print 123; print 4567; print 8;", &mut interpreter),
+            Err(LoxError::OutputLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn stops_at_call_depth_limit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_call_depth_limit(100);
+        assert_eq!(
+            crate::run("fun f() {\n  f();\n}\nf();", &mut interpreter),
+            Err(LoxError::CallDepthExceeded(2))
+        );
+
+        // The depth unwinds with the error, so the interpreter can be reused.
+        assert_eq!(
+            crate::run("fun g(n) { if (n > 0) g(n - 1); } g(99);", &mut interpreter),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn stops_at_step_limit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_step_limit(100);
+
+        assert_eq!(
+            crate::run("while (true) {}", &mut interpreter),
+            Err(LoxError::StepLimitExceeded)
+        );
+    }
 }
@@ -1,35 +1,213 @@
+// `LoxError::RuntimeError` carries the offending `Token`, and we would rather keep
+// it by value than box every error on the hot path.
+#![allow(clippy::result_large_err)]
+// Resolved expressions are looked up in `HashMap<Expr, usize>`. `Expr` hashes
+// by node id only, so the interior mutability clippy sees in `Object` never
+// affects the key.
+#![allow(clippy::mutable_key_type)]
+
+pub mod ast_json;
+pub mod ast_printer;
+pub mod coverage;
+pub mod dap;
+pub mod debugger;
 pub mod error;
+pub mod formatter;
 pub mod interpreter;
+pub mod limits;
+pub mod lint;
+pub mod lsp;
 pub mod parser;
+pub mod profiler;
+pub mod repl;
 pub mod resolver;
 pub mod scanner;
+pub mod test_runner;
+pub mod tracer;
+pub mod vm;
 
-mod ast_printer;
+mod ast_dot;
+mod call_graph;
+mod closure_compiler;
 mod environment;
 mod expr;
 mod lox_callable;
 mod object;
+mod optimizer;
+mod resolution_printer;
+mod scopes;
 mod stmt;
 mod token;
 mod token_type;
 
+use std::time::{Duration, Instant};
+
+use ast_dot::AstDot;
+use ast_json::AstJson;
+use ast_printer::AstPrinter;
+use call_graph::CallGraph;
 use error::LoxError;
 use interpreter::Interpreter;
 use parser::Parser;
+use resolution_printer::ResolutionPrinter;
 use resolver::Resolver;
 use scanner::Scanner;
+use stmt::Stmt;
+use vm::{Compiler, Vm};
 
-pub fn run(source: &str, interpreter: &mut Interpreter) -> Result<(), LoxError> {
+/// Wall-clock time spent in each phase of `run_timed` or `run_vm_timed`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub scan: Duration,
+    pub parse: Duration,
+    pub resolve: Duration,
+    pub optimize: Duration,
+    /// Bytecode compilation; always zero for the tree-walker.
+    pub compile: Duration,
+    pub execute: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.scan + self.parse + self.resolve + self.optimize + self.compile + self.execute
+    }
+}
+
+fn timed<T>(phase: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    *phase += start.elapsed();
+    result
+}
+
+fn parse(source: &str, repl: bool, timings: &mut Timings) -> Result<Vec<Stmt>, LoxError> {
     let scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens()?;
+    let tokens = timed(&mut timings.scan, || scanner.scan_tokens())?;
 
     let mut parser = Parser::new(tokens);
-    let statements = parser.parse()?;
+    timed(&mut timings.parse, || {
+        if repl {
+            parser.parse_repl()
+        } else {
+            parser.parse()
+        }
+    })
+}
+
+/// Scans, parses and resolves `source` without running it, reporting any
+/// static errors.
+pub fn check(source: &str) -> Result<(), LoxError> {
+    let statements = parse(source, false, &mut Timings::default())?;
+    Resolver::new(&mut Interpreter::new()).resolve(&statements)
+}
+
+/// The tokens the `Scanner` produces for `source`, one per line.
+pub fn dump_tokens(source: &str) -> Result<Vec<String>, LoxError> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    Ok(tokens.iter().map(|token| token.to_string()).collect())
+}
+
+/// The whole program as indented S-expressions.
+pub fn dump_ast(source: &str) -> Result<String, LoxError> {
+    let statements = parse(source, false, &mut Timings::default())?;
+    Ok(AstPrinter.print_program(&statements))
+}
+
+/// The whole program as pretty-printed JSON; see `AstJson` for the shape.
+pub fn dump_ast_json(source: &str) -> Result<String, LoxError> {
+    let statements = parse(source, false, &mut Timings::default())?;
+    let json = AstJson.program(&statements);
+    Ok(serde_json::to_string_pretty(&json).expect("JSON values always serialize"))
+}
+
+/// Every variable reference with the scope depth the `Resolver` gave it.
+pub fn dump_resolved(source: &str) -> Result<Vec<String>, LoxError> {
+    let statements = parse(source, false, &mut Timings::default())?;
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter).resolve(&statements)?;
+    Ok(ResolutionPrinter::new(&interpreter.locals).print(&statements))
+}
+
+/// The syntax tree as a Graphviz graph.
+pub fn dump_dot_ast(source: &str) -> Result<String, LoxError> {
+    let statements = parse(source, false, &mut Timings::default())?;
+    Ok(AstDot::default().print(&statements))
+}
+
+/// Which functions and methods refer to which, as a Graphviz graph.
+pub fn dump_dot_calls(source: &str) -> Result<String, LoxError> {
+    let statements = parse(source, false, &mut Timings::default())?;
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter).resolve(&statements)?;
+    Ok(CallGraph::new(&interpreter.locals).print(&statements))
+}
+
+pub fn run(source: &str, interpreter: &mut Interpreter) -> Result<(), LoxError> {
+    run_timed(source, interpreter, &mut Timings::default())
+}
 
+/// Runs one REPL entry; a trailing expression without `;` has its value printed.
+pub fn run_repl(source: &str, interpreter: &mut Interpreter) -> Result<(), LoxError> {
+    let statements = parse(source, true, &mut Timings::default())?;
+    execute(statements, interpreter, &mut Timings::default())
+}
+
+pub fn run_timed(
+    source: &str,
+    interpreter: &mut Interpreter,
+    timings: &mut Timings,
+) -> Result<(), LoxError> {
+    let statements = parse(source, false, timings)?;
+    execute(statements, interpreter, timings)
+}
+
+fn execute(
+    statements: Vec<Stmt>,
+    interpreter: &mut Interpreter,
+    timings: &mut Timings,
+) -> Result<(), LoxError> {
     let mut resolver = Resolver::new(interpreter);
-    resolver.resolve(&statements)?;
+    timed(&mut timings.resolve, || resolver.resolve(&statements))?;
+
+    // Breakpoints, coverage and traces refer to the program as written, dead
+    // code included.
+    let statements = if interpreter.watches_statements() {
+        statements
+    } else {
+        timed(&mut timings.optimize, || optimizer::optimize(statements))
+    };
+    timed(&mut timings.execute, || interpreter.interpret(&statements))?;
+
+    Ok(())
+}
+
+pub fn run_vm(source: &str, vm: &mut Vm) -> Result<(), LoxError> {
+    run_vm_timed(source, vm, &mut Timings::default())
+}
+
+/// The `--vm` counterpart of `run_repl`.
+pub fn run_vm_repl(source: &str, vm: &mut Vm) -> Result<(), LoxError> {
+    let statements = parse(source, true, &mut Timings::default())?;
+    execute_vm(statements, vm, &mut Timings::default())
+}
+
+pub fn run_vm_timed(source: &str, vm: &mut Vm, timings: &mut Timings) -> Result<(), LoxError> {
+    let statements = parse(source, false, timings)?;
+    execute_vm(statements, vm, timings)
+}
+
+fn execute_vm(statements: Vec<Stmt>, vm: &mut Vm, timings: &mut Timings) -> Result<(), LoxError> {
+    // The resolver only reports static errors here; the compiler assigns its
+    // own stack slots and upvalues.
+    let mut resolver_interpreter = Interpreter::new();
+    let mut resolver = Resolver::new(&mut resolver_interpreter);
+    timed(&mut timings.resolve, || resolver.resolve(&statements))?;
 
-    interpreter.interpret(&statements)?;
+    let statements = timed(&mut timings.optimize, || optimizer::optimize(statements));
+    let function = timed(&mut timings.compile, || {
+        Compiler::new().compile(&statements)
+    })?;
+    timed(&mut timings.execute, || vm.interpret(function))?;
 
     Ok(())
 }
@@ -0,0 +1,107 @@
+use std::time::Instant;
+
+use crate::error::LoxError;
+
+/// How deep calls may nest by default, as deep as the VM lets them.
+pub const DEFAULT_CALL_DEPTH: usize = 4096;
+
+/// The stack a thread needs to run `DEFAULT_CALL_DEPTH` calls on the
+/// tree-walker, in either execution mode. Each call takes the Rust stack
+/// through several visitor methods: a debug build of a function with a
+/// parameter, arithmetic and a nested block uses about 19 KiB per call, and
+/// a release build about 3 KiB. This leaves room for bodies three times
+/// that size in a debug build. Memory is only committed as the stack grows.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Runs `f` on a new thread with a `STACK_SIZE` stack and waits for it. A
+/// main thread's stack is usually 8 MiB, which a debug build fills in about
+/// 500 calls.
+pub fn with_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(f)
+        .expect("failed to spawn the interpreter thread")
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// What a program may still use before `Interpreter` stops it, for hosts
+/// that run untrusted code. Apart from call depth, each limit is off until
+/// it is set.
+#[derive(Debug)]
+pub(crate) struct Limits {
+    pub(crate) steps_left: Option<u64>,
+    pub(crate) deadline: Option<Instant>,
+    /// Bytes, as charged by `allocate`.
+    pub(crate) allocation_left: Option<u64>,
+    pub(crate) output_left: Option<u64>,
+    pub(crate) max_call_depth: usize,
+    call_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            steps_left: None,
+            deadline: None,
+            allocation_left: None,
+            output_left: None,
+            max_call_depth: DEFAULT_CALL_DEPTH,
+            call_depth: 0,
+        }
+    }
+}
+
+impl Limits {
+    /// Whether statements have to be counted or timed, which the closure
+    /// compiler's code does not do.
+    pub(crate) fn watch_statements(&self) -> bool {
+        self.steps_left.is_some() || self.deadline.is_some()
+    }
+
+    pub(crate) fn step(&mut self) -> Result<(), LoxError> {
+        if let Some(ref mut steps) = self.steps_left {
+            *steps = steps.checked_sub(1).ok_or(LoxError::StepLimitExceeded)?;
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(LoxError::TimeLimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /// Enters a call made on `line`. Every call entered must be left, even
+    /// when it fails.
+    pub(crate) fn enter_call(&mut self, line: usize) -> Result<(), LoxError> {
+        if self.call_depth == self.max_call_depth {
+            return Err(LoxError::CallDepthExceeded(line));
+        }
+        self.call_depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave_call(&mut self) {
+        self.call_depth -= 1;
+    }
+
+    /// Charges `bytes` for a new string or instance, before it is made, so
+    /// that one too large to afford is never allocated.
+    pub(crate) fn allocate(&mut self, bytes: usize) -> Result<(), LoxError> {
+        if let Some(ref mut allocation) = self.allocation_left {
+            *allocation = allocation
+                .checked_sub(bytes as u64)
+                .ok_or(LoxError::AllocationLimitExceeded)?;
+        }
+        Ok(())
+    }
+
+    /// Counts `bytes` about to be printed. Output that would go over the
+    /// limit is not printed at all.
+    pub(crate) fn write(&mut self, bytes: usize) -> Result<(), LoxError> {
+        if let Some(ref mut output) = self.output_left {
+            *output = output
+                .checked_sub(bytes as u64)
+                .ok_or(LoxError::OutputLimitExceeded)?;
+        }
+        Ok(())
+    }
+}
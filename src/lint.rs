@@ -0,0 +1,651 @@
+//! Warnings for code that is legal but probably wrong, for `jlox lint`.
+//!
+//! Unlike the `Resolver`'s errors, none of these stop a program from running,
+//! and each rule can be turned off in a `Config`.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    rc::Rc,
+};
+
+use serde_json::Value;
+
+use crate::{
+    error::LoxError, expr::*, interpreter::Interpreter, object::Object, parser::Parser,
+    resolver::Resolver, scanner::Scanner, scopes::Scopes, stmt::*, token::Token,
+    token_type::TokenType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// A local `var` that is never read.
+    UnusedVariable,
+    /// The value of a call to a function that never returns one.
+    NoReturnValue,
+    /// `==` or `!=` between `nil` and another literal, which is constant.
+    NilComparison,
+    /// `this` inside a function nested in a method.
+    ThisInClosure,
+    /// `{}` as a statement, or as the body of `if`, `while` or `for`.
+    EmptyBlock,
+    /// An `if` or `while` condition made only of literals. `while (true)` is
+    /// allowed, since that is also what `for (;;)` becomes.
+    ConstantCondition,
+    /// A method that takes a different number of parameters than the
+    /// superclass method it overrides.
+    OverrideArity,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::UnusedVariable,
+        Rule::NoReturnValue,
+        Rule::NilComparison,
+        Rule::ThisInClosure,
+        Rule::EmptyBlock,
+        Rule::ConstantCondition,
+        Rule::OverrideArity,
+    ];
+
+    /// The name used in config files and output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::NoReturnValue => "no-return-value",
+            Rule::NilComparison => "nil-comparison",
+            Rule::ThisInClosure => "this-in-closure",
+            Rule::EmptyBlock => "empty-block",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::OverrideArity => "override-arity",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+}
+
+/// Which rules run. Every rule is on unless turned off.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    disabled: HashSet<Rule>,
+}
+
+impl Config {
+    /// Reads a config like `{"rules": {"empty-block": false}}`. Rules that are
+    /// not mentioned stay on.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let json: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        let mut config = Config::default();
+
+        let rules = match json.get("rules") {
+            Some(Value::Object(rules)) => rules,
+            Some(_) => return Err("\"rules\" must be an object.".to_string()),
+            None => return Ok(config),
+        };
+
+        for (name, enabled) in rules {
+            let rule = Rule::from_name(name).ok_or_else(|| format!("Unknown rule '{}'.", name))?;
+            match enabled {
+                Value::Bool(true) => (),
+                Value::Bool(false) => {
+                    config.disabled.insert(rule);
+                }
+                _ => return Err(format!("Rule '{}' must be true or false.", name)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub rule: Rule,
+    pub line: usize,
+    pub message: String,
+}
+
+/// Lints a whole program. Scan, parse and resolve errors are reported as
+/// usual and returned instead of any warnings.
+pub fn lint(source: &str, config: &Config) -> Result<Vec<Warning>, LoxError> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    let empty_blocks = empty_blocks(&tokens);
+    let keywords = tokens
+        .iter()
+        .filter(|token| matches!(token.typ, TokenType::If | TokenType::While | TokenType::For))
+        .cloned()
+        .collect();
+
+    let statements = Parser::new(tokens).parse()?;
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter).resolve(&statements)?;
+
+    let mut linter = Linter::new(&interpreter.locals, keywords);
+    for line in empty_blocks {
+        linter.warn(Rule::EmptyBlock, line, "Empty block.".to_string());
+    }
+    linter.program(&statements);
+
+    let mut warnings = linter.warnings;
+    warnings.retain(|warning| config.is_enabled(warning.rule));
+    warnings.sort_by_key(|warning| warning.line);
+
+    Ok(warnings)
+}
+
+/// Lines of `{}` that are statements rather than empty function or class
+/// bodies, which the syntax tree can't tell apart from the tokens alone.
+fn empty_blocks(tokens: &[Token]) -> Vec<usize> {
+    use TokenType::{For, Identifier, If, LeftBrace, LeftParen, RightBrace, RightParen, While};
+
+    let mut lines = vec![];
+
+    for (i, pair) in tokens.windows(2).enumerate() {
+        if pair[0].typ != LeftBrace || pair[1].typ != RightBrace {
+            continue;
+        }
+
+        let is_statement = match i.checked_sub(1).map(|i| tokens[i].typ) {
+            // `class A {}` or `class B < A {}`.
+            Some(Identifier) => false,
+            // Either `fun f() {}` or the body of a control flow statement.
+            Some(RightParen) => {
+                let mut depth = 0;
+                let open = (0..i).rev().find(|&j| {
+                    match tokens[j].typ {
+                        RightParen => depth += 1,
+                        LeftParen => depth -= 1,
+                        _ => (),
+                    }
+                    depth == 0
+                });
+                open.and_then(|open| open.checked_sub(1))
+                    .is_some_and(|keyword| matches!(tokens[keyword].typ, If | While | For))
+            }
+            _ => true,
+        };
+
+        if is_statement {
+            lines.push(pair[0].line);
+        }
+    }
+
+    lines
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionType {
+    Function,
+    Method,
+}
+
+#[derive(Debug)]
+enum Binding {
+    Variable { line: usize, used: bool },
+    Function { returns_value: bool },
+    Other,
+}
+
+#[derive(Debug)]
+struct Class {
+    superclass: Option<String>,
+    arities: HashMap<String, usize>,
+}
+
+/// Walks a resolved program.
+#[derive(Debug)]
+struct Linter<'a> {
+    globals: HashMap<String, bool>,
+    scopes: Scopes<'a, Binding>,
+    functions: Vec<FunctionType>,
+    /// The `if`, `while` and `for` keywords not yet visited. Conditions have
+    /// no token of their own, but the statements come in the same order as
+    /// their keywords, with every `for` turned into one `while`.
+    keywords: VecDeque<Token>,
+    /// Classes by name, as declared so far.
+    classes: HashMap<String, Class>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Linter<'a> {
+    fn new(locals: &'a HashMap<Expr, usize>, keywords: VecDeque<Token>) -> Self {
+        Self {
+            globals: HashMap::new(),
+            scopes: Scopes::new(locals),
+            functions: vec![],
+            keywords,
+            classes: HashMap::new(),
+            warnings: vec![],
+        }
+    }
+
+    fn program(&mut self, statements: &[Stmt]) {
+        // Globals are late bound, so a function may call one declared after it.
+        for stmt in statements {
+            match stmt {
+                Stmt::Function(function) => {
+                    let returns_value = returns_value(&function.body);
+                    self.globals
+                        .insert(function.name.lexeme.clone(), returns_value);
+                }
+                Stmt::Var(var) => {
+                    self.globals.remove(&var.name.lexeme);
+                }
+                _ => (),
+            }
+        }
+
+        self.stmts(statements);
+    }
+
+    fn warn(&mut self, rule: Rule, line: usize, message: String) {
+        self.warnings.push(Warning {
+            rule,
+            line,
+            message,
+        });
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.begin();
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.end();
+
+        let mut unused: Vec<_> = scope
+            .into_iter()
+            .filter_map(|(name, binding)| match binding {
+                Binding::Variable { line, used: false } if !name.starts_with('_') => {
+                    Some((line, name))
+                }
+                _ => None,
+            })
+            .collect();
+        unused.sort();
+
+        for (line, name) in unused {
+            let message = format!("Local variable '{}' is never read.", name);
+            self.warn(Rule::UnusedVariable, line, message);
+        }
+    }
+
+    fn declare(&mut self, name: &str, binding: Binding) {
+        self.scopes.declare(name, binding);
+    }
+
+    fn lookup(&mut self, expr: &Expr, name: &Token) -> Option<&mut Binding> {
+        self.scopes.get_mut(expr, name)
+    }
+
+    fn function(&mut self, function: &StmtFunction, ftype: FunctionType) {
+        self.functions.push(ftype);
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(&param.lexeme, Binding::Other);
+        }
+        self.stmts(&function.body);
+        self.end_scope();
+        self.functions.pop();
+    }
+
+    /// `used` is false when the call is a statement of its own.
+    fn call(&mut self, expr: &ExprCall, used: bool) {
+        if let (true, Expr::Variable(ref callee)) = (used, &*expr.callee) {
+            let returns_value = if self.scopes.is_local(&expr.callee) {
+                match self.lookup(&expr.callee, &callee.name) {
+                    Some(Binding::Function { returns_value }) => Some(*returns_value),
+                    _ => None,
+                }
+            } else {
+                self.globals.get(&callee.name.lexeme).copied()
+            };
+            if returns_value == Some(false) {
+                let message = format!(
+                    "'{}' never returns a value, so this call is always nil.",
+                    callee.name.lexeme
+                );
+                self.warn(Rule::NoReturnValue, expr.paren.line, message);
+            }
+        }
+
+        self.expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.expr(argument);
+        }
+    }
+
+    fn condition(&mut self, condition: &Expr) {
+        let keyword = self.keywords.pop_front();
+        if let (true, Some(keyword)) = (is_constant(condition), keyword) {
+            let message = format!("This '{}' condition is always the same.", keyword.lexeme);
+            self.warn(Rule::ConstantCondition, keyword.line, message);
+        }
+        self.expr(condition);
+    }
+
+    fn superclass_arity(
+        &self,
+        superclass: Option<&String>,
+        method: &str,
+    ) -> Option<(String, usize)> {
+        let mut class = superclass;
+        // A chain can't be longer than the number of classes, unless it loops.
+        for _ in 0..=self.classes.len() {
+            let name = class?;
+            let info = self.classes.get(name)?;
+            if let Some(&arity) = info.arities.get(method) {
+                return Some((name.clone(), arity));
+            }
+            class = info.superclass.as_ref();
+        }
+        None
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            stmt.accept(self);
+        }
+    }
+}
+
+/// Whether a function body has a `return` with a value, not counting the
+/// bodies of functions declared inside it.
+fn returns_value(statements: &[Stmt]) -> bool {
+    statements.iter().any(|stmt| match stmt {
+        Stmt::Return(stmt) => stmt.value.is_some(),
+        Stmt::Block(block) => returns_value(&block.statements),
+        Stmt::If(stmt) => {
+            returns_value(std::slice::from_ref(&stmt.then_branch))
+                || stmt
+                    .else_branch
+                    .as_ref()
+                    .is_some_and(|branch| returns_value(std::slice::from_ref(branch)))
+        }
+        Stmt::While(stmt) => returns_value(std::slice::from_ref(&stmt.body)),
+        _ => false,
+    })
+}
+
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) => true,
+        Expr::Grouping(expr) => is_constant(&expr.expression),
+        Expr::Unary(expr) => is_constant(&expr.right),
+        Expr::Binary(expr) => is_constant(&expr.left) && is_constant(&expr.right),
+        Expr::Logical(expr) => is_constant(&expr.left) && is_constant(&expr.right),
+        _ => false,
+    }
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal(_))
+}
+
+fn is_nil(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal(literal) if literal.value == Object::Null)
+}
+
+impl ExprVisitor<()> for Linter<'_> {
+    fn visit_literal_expr(&mut self, _expr: &ExprLiteral) {}
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) {
+        self.expr(&expr.right);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) {
+        use TokenType::{BangEqual, EqualEqual};
+
+        let (left, right) = (&*expr.left, &*expr.right);
+        if matches!(expr.operator.typ, EqualEqual | BangEqual)
+            && ((is_nil(left) && is_literal(right)) || (is_literal(left) && is_nil(right)))
+        {
+            let message = format!(
+                "Comparing nil to a literal with '{}' always gives the same result.",
+                expr.operator.lexeme
+            );
+            self.warn(Rule::NilComparison, expr.operator.line, message);
+        }
+
+        self.expr(left);
+        self.expr(right);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) {
+        self.expr(&expr.expression);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) {
+        if let Some(Binding::Variable { used, .. }) =
+            self.lookup(&Expr::Variable(expr.clone()), &expr.name)
+        {
+            *used = true;
+        }
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) {
+        self.expr(&expr.value);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) {
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) {
+        self.call(expr, true);
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) {
+        self.expr(&expr.object);
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) {
+        self.expr(&expr.object);
+        self.expr(&expr.value);
+    }
+
+    fn visit_this_expr(&mut self, expr: &ExprThis) {
+        // The resolver already rejects `this` outside of classes.
+        if self.functions.last() == Some(&FunctionType::Function) {
+            self.warn(
+                Rule::ThisInClosure,
+                expr.keyword.line,
+                "'this' is captured by a function inside a method.".to_string(),
+            );
+        }
+    }
+
+    fn visit_super_expr(&mut self, _expr: &ExprSuper) {}
+}
+
+impl StmtVisitor<()> for Linter<'_> {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) {
+        match stmt.expression {
+            Expr::Call(ref call) => self.call(call, false),
+            ref expression => self.expr(expression),
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) {
+        if let Some(ref initializer) = stmt.initializer {
+            self.expr(initializer);
+        }
+        let binding = Binding::Variable {
+            line: stmt.name.line,
+            used: false,
+        };
+        self.declare(&stmt.name.lexeme, binding);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) {
+        self.begin_scope();
+        self.stmts(&stmt.statements);
+        self.end_scope();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) {
+        self.condition(&stmt.condition);
+        stmt.then_branch.accept(self);
+        if let Some(ref else_branch) = stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) {
+        match stmt.condition {
+            Expr::Literal(ref literal) if literal.value == Object::Bool(true) => {
+                self.keywords.pop_front();
+            }
+            ref condition => self.condition(condition),
+        }
+        stmt.body.accept(self);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) {
+        let binding = Binding::Function {
+            returns_value: returns_value(&stmt.body),
+        };
+        self.declare(&stmt.name.lexeme, binding);
+        self.function(stmt, FunctionType::Function);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) {
+        if let Some(ref value) = stmt.value {
+            self.expr(value);
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) {
+        self.declare(&stmt.name.lexeme, Binding::Other);
+
+        let superclass = match stmt.superclass {
+            Some(Expr::Variable(ref variable)) => {
+                self.expr(&Expr::Variable(variable.clone()));
+                Some(variable.name.lexeme.clone())
+            }
+            _ => None,
+        };
+
+        let methods: Vec<&Rc<StmtFunction>> = stmt
+            .methods
+            .iter()
+            .filter_map(|method| match method {
+                Stmt::Function(function) => Some(function),
+                _ => None,
+            })
+            .collect();
+
+        for method in &methods {
+            let name = &method.name.lexeme;
+            if let Some((parent, arity)) = self.superclass_arity(superclass.as_ref(), name) {
+                if arity != method.params.len() {
+                    let message = format!(
+                        "'{}.{}' takes {} parameter(s) but overrides '{}.{}', which takes {}.",
+                        stmt.name.lexeme,
+                        name,
+                        method.params.len(),
+                        parent,
+                        name,
+                        arity
+                    );
+                    self.warn(Rule::OverrideArity, method.name.line, message);
+                }
+            }
+        }
+
+        let arities = methods
+            .iter()
+            .map(|method| (method.name.lexeme.clone(), method.params.len()))
+            .collect();
+        self.classes.insert(
+            stmt.name.lexeme.clone(),
+            Class {
+                superclass: superclass.clone(),
+                arities,
+            },
+        );
+
+        if superclass.is_some() {
+            self.begin_scope();
+            self.declare("super", Binding::Other);
+        }
+        self.begin_scope();
+        self.declare("this", Binding::Other);
+
+        for method in methods {
+            self.function(method, FunctionType::Method);
+        }
+
+        self.end_scope();
+        if superclass.is_some() {
+            self.end_scope();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rules(source: &str) -> Vec<(&'static str, usize)> {
+        lint(source, &Config::default())
+            .unwrap()
+            .into_iter()
+            .map(|warning| (warning.rule.name(), warning.line))
+            .collect()
+    }
+
+    #[test]
+    fn reports_each_rule() {
+        let source = "
+fun log(x) { print x; }
+fun main() {
+  var unused = 1;
+  var _ignored = 2;
+  var y = log(1);
+  if (nil == nil) {}
+  while (false) print y;
+  for (;;) { return; }
+}
+class A { m(a) { fun f() { return this; } return f; } }
+class B < A { m(a, b) {} }
+";
+        assert_eq!(
+            rules(source),
+            vec![
+                ("unused-variable", 4),
+                ("no-return-value", 6),
+                ("empty-block", 7),
+                ("constant-condition", 7),
+                ("nil-comparison", 7),
+                ("constant-condition", 8),
+                ("this-in-closure", 11),
+                ("override-arity", 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_rule_toggles() {
+        let config = Config::from_json(r#"{"rules": {"empty-block": false}}"#).unwrap();
+        assert!(!config.is_enabled(Rule::EmptyBlock));
+        assert!(config.is_enabled(Rule::UnusedVariable));
+
+        assert!(Config::from_json(r#"{"rules": {"no-such-rule": false}}"#).is_err());
+        assert!(Config::from_json(r#"{"rules": {"empty-block": 0}}"#).is_err());
+    }
+}
@@ -1,8 +1,8 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
-    environment::Environment, error::LoxError, interpreter::Interpreter, object::Object, stmt::*,
-    token::Token,
+    closure_compiler::CompiledBlock, environment::Environment, error::LoxError,
+    interpreter::Interpreter, object::Object, stmt::*, token::Token,
 };
 
 pub trait LoxCallable {
@@ -18,6 +18,17 @@ pub enum CallableKind {
     Class(LoxClass),
 }
 
+impl CallableKind {
+    /// The function's, native's or class's name, without decoration.
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Self::Function(callable) => &callable.declaration.name.lexeme,
+            Self::Native(callable) => callable.name,
+            Self::Class(callable) => &callable.name,
+        }
+    }
+}
+
 impl std::fmt::Display for CallableKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -28,6 +39,22 @@ impl std::fmt::Display for CallableKind {
     }
 }
 
+// Callables and instances compare by identity, like objects in the book's jlox.
+impl PartialEq for CallableKind {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Function(a), Self::Function(b)) => {
+                Rc::ptr_eq(&a.declaration, &b.declaration) && Rc::ptr_eq(&a.closure, &b.closure)
+            }
+            (Self::Native(a), Self::Native(b)) => {
+                std::ptr::fn_addr_eq(a.pointer, b.pointer) && a.arity == b.arity
+            }
+            (Self::Class(a), Self::Class(b)) => Rc::ptr_eq(&a.methods, &b.methods),
+            _ => false,
+        }
+    }
+}
+
 impl LoxCallable for CallableKind {
     fn call(
         &self,
@@ -52,19 +79,37 @@ impl LoxCallable for CallableKind {
 
 #[derive(Debug, Clone)]
 pub struct LoxFunction {
-    declaration: StmtFunction,
+    declaration: Rc<StmtFunction>,
+    // Set when the function was produced by the closure backend; the body then
+    // runs from here instead of walking `declaration.body`.
+    compiled: Option<Rc<CompiledBlock>>,
     closure: Rc<RefCell<Environment>>,
     is_initializer: bool,
 }
 
 impl LoxFunction {
     pub fn new(
-        declaration: StmtFunction,
+        declaration: Rc<StmtFunction>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            declaration,
+            compiled: None,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub(crate) fn new_compiled(
+        declaration: Rc<StmtFunction>,
+        compiled: Rc<CompiledBlock>,
         closure: Rc<RefCell<Environment>>,
         is_initializer: bool,
     ) -> Self {
         Self {
             declaration,
+            compiled: Some(compiled),
             closure,
             is_initializer,
         }
@@ -75,11 +120,10 @@ impl LoxFunction {
 
         environment.define("this".to_string(), Object::Instance(instance));
 
-        LoxFunction::new(
-            self.declaration,
-            Rc::new(RefCell::new(environment)),
-            self.is_initializer,
-        )
+        LoxFunction {
+            closure: Rc::new(RefCell::new(environment)),
+            ..self
+        }
     }
 }
 
@@ -101,15 +145,28 @@ impl LoxCallable for LoxFunction {
             environment.define(param.lexeme.clone(), obj.clone());
         }
 
-        if let Err(LoxError::Return(return_value)) =
-            interpreter.execute_block(&self.declaration.body, Rc::new(RefCell::new(environment)))
-        {
-            if self.is_initializer {
-                let this = self.closure.as_ref().borrow_mut().get_at(0, "this");
-                return Ok(this);
+        let environment = Rc::new(RefCell::new(environment));
+        let name = &self.declaration.name.lexeme;
+        interpreter.limits.enter_call(interpreter.call_line)?;
+        interpreter.enter_function(name);
+        let result = interpreter.profiled(name, |interpreter| match self.compiled {
+            Some(ref body) => interpreter.execute_compiled_block(body, environment),
+            None => interpreter.execute_block(&self.declaration.body, environment),
+        });
+        interpreter.leave_function();
+        interpreter.limits.leave_call();
+
+        match result {
+            Err(LoxError::Return(return_value)) => {
+                if self.is_initializer {
+                    let this = self.closure.as_ref().borrow_mut().get_at(0, "this");
+                    return Ok(this);
+                }
+
+                return Ok(return_value);
             }
-
-            return Ok(return_value);
+            Err(error) => return Err(error),
+            Ok(()) => (),
         }
 
         if self.is_initializer {
@@ -126,16 +183,22 @@ impl LoxCallable for LoxFunction {
 
 #[derive(Debug, Clone)]
 pub struct NativeFunction {
+    name: &'static str,
     pointer: fn(&mut Interpreter, &[Object]) -> Result<Object, LoxError>,
     arity: usize,
 }
 
 impl NativeFunction {
     pub fn new(
+        name: &'static str,
         pointer: fn(&mut Interpreter, &[Object]) -> Result<Object, LoxError>,
         arity: usize,
     ) -> Self {
-        Self { pointer, arity }
+        Self {
+            name,
+            pointer,
+            arity,
+        }
     }
 }
 
@@ -151,7 +214,9 @@ impl LoxCallable for NativeFunction {
         interpreter: &mut Interpreter,
         arguments: &[Object],
     ) -> Result<Object, LoxError> {
-        (self.pointer)(interpreter, arguments)
+        interpreter.profiled(self.name, |interpreter| {
+            (self.pointer)(interpreter, arguments)
+        })
     }
 
     fn arity(&self) -> usize {
@@ -204,15 +269,20 @@ impl LoxCallable for LoxClass {
         interpreter: &mut Interpreter,
         arguments: &[Object],
     ) -> Result<Object, LoxError> {
-        let instance = LoxInstance::new(self.clone());
-
-        if let Some(initializer) = self.find_method("init") {
-            initializer
-                .bind(instance.clone())
-                .call(interpreter, arguments)?;
-        }
+        interpreter.profiled(&self.name, |interpreter| {
+            interpreter
+                .limits
+                .allocate(std::mem::size_of::<LoxInstance>())?;
+            let instance = LoxInstance::new(self.clone());
+
+            if let Some(initializer) = self.find_method("init") {
+                initializer
+                    .bind(instance.clone())
+                    .call(interpreter, arguments)?;
+            }
 
-        Ok(Object::Instance(instance))
+            Ok(Object::Instance(instance))
+        })
     }
 
     fn arity(&self) -> usize {
@@ -258,6 +328,24 @@ impl LoxInstance {
     pub fn set(&mut self, name: Token, value: Object) {
         self.fields.borrow_mut().insert(name.lexeme, value);
     }
+
+    /// Fields sorted by name; methods are not included.
+    pub fn fields(&self) -> Vec<(String, Object)> {
+        let mut fields: Vec<_> = self
+            .fields
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+        fields
+    }
+}
+
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.fields, &other.fields)
+    }
 }
 
 impl std::fmt::Display for LoxInstance {
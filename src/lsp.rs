@@ -0,0 +1,483 @@
+//! A Language Server Protocol server, run by the `jlox-lsp` binary.
+//!
+//! Messages are JSON-RPC over stdio. Documents are synced whole, and each
+//! version is scanned, parsed and resolved to publish its errors and to
+//! index its symbols for definitions, references, hovers and outlines.
+
+mod symbols;
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    error::{self, Diagnostic},
+    interpreter::Interpreter,
+    lint,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::{self, Scanner},
+    token::Token,
+};
+use symbols::{Index, SymbolKind};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// An error code and message for a response.
+type ResponseError = (i64, String);
+
+/// A symbol under the cursor, with the name token there.
+type Occurrence<'a> = (&'a Document, &'a Index, &'a Token, usize);
+
+/// Reads one message, or `None` at the end of input. A body that isn't JSON
+/// is an `InvalidData` error; the stream is still usable after it.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Serves one client until it sends `exit` or closes the input, and returns
+/// the exit status the protocol asks for.
+pub fn run(mut input: impl BufRead, mut output: impl Write) -> io::Result<i32> {
+    let mut server = Server::default();
+
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return Ok(if server.shutdown { 0 } else { 1 }),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                let response = error_response(Value::Null, PARSE_ERROR, &err.to_string());
+                write_message(&mut output, &response)?;
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+
+        for reply in server.handle(message) {
+            write_message(&mut output, &reply)?;
+        }
+        if let Some(code) = server.exit {
+            return Ok(code);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Document {
+    text: String,
+    /// From the last version without errors, so that navigation keeps
+    /// working while an edit is half done.
+    index: Option<Index>,
+}
+
+impl Document {
+    /// LSP positions count UTF-16 code units; token columns count chars.
+    fn position(&self, line: usize, column: usize) -> Value {
+        let text = self
+            .text
+            .split('\n')
+            .nth(line.saturating_sub(1))
+            .unwrap_or("");
+        let character: usize = text.chars().take(column).map(char::len_utf16).sum();
+        json!({ "line": line.saturating_sub(1), "character": character })
+    }
+
+    /// The 1-based line and char column of an LSP position.
+    fn line_column(&self, position: &Value) -> Option<(usize, usize)> {
+        let line = position.get("line")?.as_u64()? as usize;
+        let character = position.get("character")?.as_u64()? as usize;
+        let text = self.text.split('\n').nth(line).unwrap_or("");
+
+        let mut units = 0;
+        let column = text
+            .chars()
+            .take_while(|c| {
+                units += c.len_utf16();
+                units <= character
+            })
+            .count();
+        Some((line + 1, column))
+    }
+
+    fn range(&self, token: &Token) -> Value {
+        let end = token.column + token.lexeme.chars().count();
+        json!({
+            "start": self.position(token.line, token.column),
+            "end": self.position(token.line, end),
+        })
+    }
+
+    fn line_range(&self, line: usize) -> Value {
+        let text = self
+            .text
+            .split('\n')
+            .nth(line.saturating_sub(1))
+            .unwrap_or("");
+        json!({
+            "start": self.position(line, 0),
+            "end": self.position(line, text.chars().count()),
+        })
+    }
+}
+
+/// Protocol state. `handle` takes each message from the client and returns
+/// the messages to send back.
+#[derive(Debug, Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exit: Option<i32>,
+}
+
+impl Server {
+    pub fn handle(&mut self, message: Value) -> Vec<Value> {
+        let method = message.get("method").and_then(Value::as_str);
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        match (method, message.get("id").cloned()) {
+            (Some(method), Some(id)) => {
+                let result = if self.shutdown {
+                    Err((INVALID_REQUEST, "The server is shutting down.".to_string()))
+                } else {
+                    self.request(method, &params)
+                };
+                let response = match result {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => error_response(id, code, &message),
+                };
+                vec![response]
+            }
+            (Some(method), None) => self.notification(method, &params),
+            // A response to a request we never make.
+            (None, _) => vec![],
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, ResponseError> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "jlox-lsp", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/completion" => self.completion(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        match (method, uri) {
+            ("exit", _) => {
+                self.exit = Some(if self.shutdown { 0 } else { 1 });
+                vec![]
+            }
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params.pointer("/textDocument/text").and_then(Value::as_str);
+                self.update(uri, text.unwrap_or_default())
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // With full sync, the last change is the whole document.
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Value::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Value::as_str);
+                match text {
+                    Some(text) => self.update(uri, text),
+                    None => vec![],
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                vec![publish_diagnostics(&uri, vec![])]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn update(&mut self, uri: String, text: &str) -> Vec<Value> {
+        let (errors, index) = analyze(text);
+        // Lint reports nothing new for a program with errors.
+        let warnings = match index {
+            Some(_) => error::capture(|| lint::lint(text, &lint::Config::default())).0,
+            None => Ok(vec![]),
+        };
+
+        let document = self.documents.entry(uri.clone()).or_insert(Document {
+            text: String::new(),
+            index: None,
+        });
+        document.text = text.to_string();
+        if index.is_some() {
+            document.index = index;
+        }
+
+        let mut diagnostics: Vec<Value> = errors
+            .iter()
+            .map(|error| {
+                let range = match error.token {
+                    Some(ref token) => document.range(token),
+                    None => document.line_range(error.line),
+                };
+                json!({
+                    "range": range,
+                    "severity": 1,
+                    "source": "jlox",
+                    "message": error.message,
+                })
+            })
+            .collect();
+        for warning in warnings.unwrap_or_default() {
+            diagnostics.push(json!({
+                "range": document.line_range(warning.line),
+                "severity": 2,
+                "source": "jlox lint",
+                "code": warning.rule.name(),
+                "message": warning.message,
+            }));
+        }
+
+        vec![publish_diagnostics(&uri, diagnostics)]
+    }
+
+    /// The document and the symbol under the cursor in `params`.
+    fn symbol_at(&self, params: &Value) -> Result<Option<Occurrence<'_>>, ResponseError> {
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "Missing textDocument.uri.".to_string()))?;
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or((INVALID_PARAMS, format!("'{}' is not open.", uri)))?;
+        let (line, column) = params
+            .get("position")
+            .and_then(|position| document.line_column(position))
+            .ok_or((INVALID_PARAMS, "Missing position.".to_string()))?;
+
+        Ok(document.index.as_ref().and_then(|index| {
+            let (token, symbol) = index.occurrence_at(line, column)?;
+            Some((document, index, token, *symbol))
+        }))
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, ResponseError> {
+        let uri = &params["textDocument"]["uri"];
+        Ok(match self.symbol_at(params)? {
+            Some((document, index, _, symbol)) => {
+                let token = &index.symbols[symbol].token;
+                json!({ "uri": uri, "range": document.range(token) })
+            }
+            None => Value::Null,
+        })
+    }
+
+    fn references(&self, params: &Value) -> Result<Value, ResponseError> {
+        let uri = &params["textDocument"]["uri"];
+        let include_declaration = params
+            .pointer("/context/includeDeclaration")
+            .and_then(Value::as_bool)
+            .unwrap_or(true);
+
+        Ok(match self.symbol_at(params)? {
+            Some((document, index, _, symbol)) => {
+                let declaration = &index.symbols[symbol].token;
+                let locations: Vec<Value> = index
+                    .occurrences_of(symbol)
+                    .filter(|token| include_declaration || *token != declaration)
+                    .map(|token| json!({ "uri": uri, "range": document.range(token) }))
+                    .collect();
+                json!(locations)
+            }
+            None => Value::Null,
+        })
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, ResponseError> {
+        Ok(match self.symbol_at(params)? {
+            Some((document, index, token, symbol)) => {
+                let symbol = &index.symbols[symbol];
+                let mut value = format!("```lox\n{}\n```", symbol.detail);
+                if symbol.kind == SymbolKind::Parameter {
+                    let function = symbol.parent.map(|parent| &index.symbols[parent].detail);
+                    value.push_str(&format!("\nParameter of `{}`", function.unwrap()));
+                }
+                value.push_str(&format!("\n\nDeclared on line {}.", symbol.token.line));
+                json!({
+                    "contents": { "kind": "markdown", "value": value },
+                    "range": document.range(token),
+                })
+            }
+            None => Value::Null,
+        })
+    }
+
+    fn document_symbols(&self, params: &Value) -> Result<Value, ResponseError> {
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let Some((document, Some(index))) = self
+            .documents
+            .get(uri)
+            .map(|document| (document, document.index.as_ref()))
+        else {
+            return Ok(json!([]));
+        };
+
+        // Functions, classes and methods, with global variables at the top
+        // level. Locals and parameters would only be noise in an outline.
+        fn children(document: &Document, index: &Index, parent: Option<usize>) -> Vec<Value> {
+            index
+                .symbols
+                .iter()
+                .enumerate()
+                .filter(|(_, symbol)| symbol.parent == parent)
+                .filter_map(|(id, symbol)| {
+                    let kind = match symbol.kind {
+                        SymbolKind::Class => 5,
+                        SymbolKind::Method => 6,
+                        SymbolKind::Function => 12,
+                        SymbolKind::Variable if parent.is_none() => 13,
+                        _ => return None,
+                    };
+                    let range = document.range(&symbol.token);
+                    Some(json!({
+                        "name": symbol.token.lexeme,
+                        "detail": symbol.detail,
+                        "kind": kind,
+                        "range": range,
+                        "selectionRange": range,
+                        "children": children(document, index, Some(id)),
+                    }))
+                })
+                .collect()
+        }
+
+        Ok(json!(children(document, index, None)))
+    }
+
+    fn completion(&self, params: &Value) -> Result<Value, ResponseError> {
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        let mut items: Vec<Value> = scanner::keywords()
+            .map(|keyword| json!({ "label": keyword, "kind": 14 }))
+            .collect();
+        for native in Interpreter::new().global_names() {
+            items.push(json!({ "label": native, "kind": 3 }));
+        }
+
+        let index = self
+            .documents
+            .get(uri)
+            .and_then(|document| document.index.as_ref());
+        let mut seen = std::collections::HashSet::new();
+        for symbol in index.iter().flat_map(|index| &index.symbols) {
+            if !seen.insert(&symbol.token.lexeme) {
+                continue;
+            }
+            let kind = match symbol.kind {
+                SymbolKind::Class => 7,
+                SymbolKind::Method => 2,
+                SymbolKind::Function => 3,
+                SymbolKind::Variable | SymbolKind::Parameter => 6,
+            };
+            items.push(json!({
+                "label": symbol.token.lexeme,
+                "kind": kind,
+                "detail": symbol.detail,
+            }));
+        }
+
+        Ok(json!(items))
+    }
+}
+
+/// Scans, parses and resolves `text`, returning the errors, and the index
+/// if there were none.
+fn analyze(text: &str) -> (Vec<Diagnostic>, Option<Index>) {
+    let (index, diagnostics) = error::capture(|| {
+        let tokens = Scanner::new(text).scan_tokens().ok()?;
+        let statements = Parser::new(tokens).parse().ok()?;
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter).resolve(&statements).ok()?;
+        Some(Index::build(&statements, &interpreter.locals))
+    });
+
+    (diagnostics, index)
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
@@ -0,0 +1,407 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{expr::*, scopes::Scopes, stmt::*, token::Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Parameter,
+    Function,
+    Method,
+    Class,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    /// The name in the declaration.
+    pub token: Token,
+    /// The declaration as it reads in source, like `fun add(a, b)`.
+    pub detail: String,
+    /// The function or class the symbol is declared in.
+    pub parent: Option<usize>,
+}
+
+/// Every declaration in a program, and every place each one is used.
+#[derive(Debug, Default)]
+pub struct Index {
+    pub symbols: Vec<Symbol>,
+    /// Name tokens of declarations and uses, with the symbol they name.
+    pub occurrences: Vec<(Token, usize)>,
+}
+
+impl Index {
+    /// Indexes a program the `Resolver` has already resolved into `locals`.
+    pub fn build(statements: &[Stmt], locals: &HashMap<Expr, usize>) -> Self {
+        let mut indexer = Indexer {
+            index: Index::default(),
+            globals: HashMap::new(),
+            unresolved: vec![],
+            scopes: Scopes::new(locals),
+            parents: vec![],
+            classes: HashMap::new(),
+            current_classes: vec![],
+        };
+        indexer.stmts(statements);
+
+        // Globals are late bound, so a use may come before the declaration.
+        for token in std::mem::take(&mut indexer.unresolved) {
+            if let Some(&symbol) = indexer.globals.get(&token.lexeme) {
+                indexer.index.occurrences.push((token, symbol));
+            }
+        }
+        indexer
+            .index
+            .occurrences
+            .sort_by_key(|(token, _)| (token.line, token.column));
+
+        indexer.index
+    }
+
+    /// The occurrence covering a 1-based line and 0-based column, if any.
+    pub fn occurrence_at(&self, line: usize, column: usize) -> Option<&(Token, usize)> {
+        self.occurrences.iter().find(|(token, _)| {
+            token.line == line
+                && token.column <= column
+                && column <= token.column + token.lexeme.chars().count()
+        })
+    }
+
+    pub fn occurrences_of(&self, symbol: usize) -> impl Iterator<Item = &Token> {
+        self.occurrences
+            .iter()
+            .filter(move |(_, s)| *s == symbol)
+            .map(|(token, _)| token)
+    }
+}
+
+#[derive(Debug)]
+struct Class {
+    superclass: Option<usize>,
+    methods: HashMap<String, usize>,
+}
+
+struct Indexer<'a> {
+    index: Index,
+    globals: HashMap<String, usize>,
+    unresolved: Vec<Token>,
+    /// Names declared in each scope. `this` and `super` are not symbols.
+    scopes: Scopes<'a, Option<usize>>,
+    /// Enclosing functions and classes, innermost last.
+    parents: Vec<usize>,
+    classes: HashMap<usize, Class>,
+    current_classes: Vec<usize>,
+}
+
+impl Indexer<'_> {
+    fn add(&mut self, kind: SymbolKind, token: &Token, detail: String) -> usize {
+        let id = self.index.symbols.len();
+        self.index.symbols.push(Symbol {
+            kind,
+            token: token.clone(),
+            detail,
+            parent: self.parents.last().copied(),
+        });
+        self.index.occurrences.push((token.clone(), id));
+        id
+    }
+
+    fn declare(&mut self, kind: SymbolKind, token: &Token, detail: String) -> usize {
+        let id = self.add(kind, token, detail);
+        // The first declaration of a global is the one to go to.
+        if !self.scopes.declare(&token.lexeme, Some(id)) {
+            self.globals.entry(token.lexeme.clone()).or_insert(id);
+        }
+        id
+    }
+
+    /// `None` for globals, otherwise the local symbol, if it is one.
+    fn local(&self, expr: &Expr, name: &Token) -> Option<Option<usize>> {
+        if !self.scopes.is_local(expr) {
+            return None;
+        }
+        Some(self.scopes.get(expr, name).copied().flatten())
+    }
+
+    fn reference(&mut self, expr: &Expr, name: &Token) {
+        match self.local(expr, name) {
+            Some(Some(symbol)) => self.index.occurrences.push((name.clone(), symbol)),
+            Some(None) => (),
+            None => self.unresolved.push(name.clone()),
+        }
+    }
+
+    fn find_method(&self, class: Option<usize>, name: &Token) -> Option<usize> {
+        let mut class = class;
+        for _ in 0..=self.classes.len() {
+            let info = self.classes.get(&class?)?;
+            if let Some(&method) = info.methods.get(&name.lexeme) {
+                return Some(method);
+            }
+            class = info.superclass;
+        }
+        None
+    }
+
+    fn method_reference(&mut self, class: Option<usize>, name: &Token) {
+        if let Some(method) = self.find_method(class, name) {
+            self.index.occurrences.push((name.clone(), method));
+        }
+    }
+
+    fn function(&mut self, id: usize, function: &StmtFunction) {
+        self.parents.push(id);
+        self.scopes.begin();
+        for param in &function.params {
+            self.declare(SymbolKind::Parameter, param, param.lexeme.clone());
+        }
+        self.stmts(&function.body);
+        self.scopes.end();
+        self.parents.pop();
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            stmt.accept(self);
+        }
+    }
+}
+
+fn signature(function: &StmtFunction) -> String {
+    let params: Vec<&str> = function.params.iter().map(|p| p.lexeme.as_str()).collect();
+    format!("{}({})", function.name.lexeme, params.join(", "))
+}
+
+impl ExprVisitor<()> for Indexer<'_> {
+    fn visit_literal_expr(&mut self, _expr: &ExprLiteral) {}
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) {
+        self.expr(&expr.right);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) {
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) {
+        self.expr(&expr.expression);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) {
+        self.reference(&Expr::Variable(expr.clone()), &expr.name);
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) {
+        self.expr(&expr.value);
+        self.reference(&Expr::Assign(expr.clone()), &expr.name);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) {
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) {
+        self.expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.expr(argument);
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) {
+        if let Expr::This(_) = *expr.object {
+            self.method_reference(self.current_classes.last().copied(), &expr.name);
+        }
+        self.expr(&expr.object);
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) {
+        self.expr(&expr.object);
+        self.expr(&expr.value);
+    }
+
+    fn visit_this_expr(&mut self, _expr: &ExprThis) {}
+
+    fn visit_super_expr(&mut self, expr: &ExprSuper) {
+        let superclass = self
+            .current_classes
+            .last()
+            .and_then(|class| self.classes.get(class))
+            .and_then(|class| class.superclass);
+        self.method_reference(superclass, &expr.method);
+    }
+}
+
+impl StmtVisitor<()> for Indexer<'_> {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) {
+        if let Some(ref initializer) = stmt.initializer {
+            self.expr(initializer);
+        }
+        let detail = format!("var {}", stmt.name.lexeme);
+        self.declare(SymbolKind::Variable, &stmt.name, detail);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) {
+        self.scopes.begin();
+        self.stmts(&stmt.statements);
+        self.scopes.end();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) {
+        self.expr(&stmt.condition);
+        stmt.then_branch.accept(self);
+        if let Some(ref else_branch) = stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) {
+        self.expr(&stmt.condition);
+        stmt.body.accept(self);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) {
+        let detail = format!("fun {}", signature(stmt));
+        let id = self.declare(SymbolKind::Function, &stmt.name, detail);
+        self.function(id, stmt);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) {
+        if let Some(ref value) = stmt.value {
+            self.expr(value);
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) {
+        let mut detail = format!("class {}", stmt.name.lexeme);
+        if let Some(Expr::Variable(ref superclass)) = stmt.superclass {
+            detail = format!("{} < {}", detail, superclass.name.lexeme);
+        }
+        let id = self.declare(SymbolKind::Class, &stmt.name, detail);
+
+        let mut superclass = None;
+        if let Some(Expr::Variable(ref variable)) = stmt.superclass {
+            let expr = Expr::Variable(variable.clone());
+            // A global superclass has to be declared before it is inherited.
+            superclass = match self.local(&expr, &variable.name) {
+                Some(symbol) => symbol,
+                None => self.globals.get(&variable.name.lexeme).copied(),
+            };
+            self.expr(&expr);
+        }
+
+        // Methods are declared up front; they may call each other in any order.
+        self.parents.push(id);
+        let mut methods = vec![];
+        for method in &stmt.methods {
+            if let Stmt::Function(function) = method {
+                let detail = format!("{}.{}", stmt.name.lexeme, signature(function));
+                let method_id = self.add(SymbolKind::Method, &function.name, detail);
+                methods.push((function, method_id));
+            }
+        }
+        self.parents.pop();
+
+        let names = methods
+            .iter()
+            .map(|(function, id)| (function.name.lexeme.clone(), *id))
+            .collect();
+        self.classes.insert(
+            id,
+            Class {
+                superclass,
+                methods: names,
+            },
+        );
+
+        if stmt.superclass.is_some() {
+            self.scopes.begin();
+            self.scopes.declare("super", None);
+        }
+        self.scopes.begin();
+        self.scopes.declare("this", None);
+        self.current_classes.push(id);
+
+        for (function, method_id) in methods {
+            self.function(method_id, function);
+        }
+
+        self.current_classes.pop();
+        self.scopes.end();
+        if stmt.superclass.is_some() {
+            self.scopes.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{interpreter::Interpreter, parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    use super::*;
+
+    fn index(source: &str) -> Index {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter)
+            .resolve(&statements)
+            .unwrap();
+        Index::build(&statements, &interpreter.locals)
+    }
+
+    #[test]
+    fn links_uses_to_declarations() {
+        let index = index(
+            "var a = 1;\n\
+             fun f(a) { return a + g(); }\n\
+             fun g() { return a; }\n\
+             class A { m() { return this.m; } }",
+        );
+
+        let positions = |line, column| {
+            let &(_, symbol) = index.occurrence_at(line, column).unwrap();
+            let symbol = &index.symbols[symbol];
+            let uses: Vec<_> = index
+                .occurrences_of(
+                    index
+                        .symbols
+                        .iter()
+                        .position(|s| s.token == symbol.token)
+                        .unwrap(),
+                )
+                .map(|token| (token.line, token.column))
+                .collect();
+            (symbol.detail.clone(), uses)
+        };
+
+        // The parameter shadows the global.
+        assert_eq!(positions(2, 18), ("a".to_string(), vec![(2, 6), (2, 18)]));
+        assert_eq!(
+            positions(3, 17),
+            ("var a".to_string(), vec![(1, 4), (3, 17)])
+        );
+        // Used before it is declared.
+        assert_eq!(
+            positions(2, 22),
+            ("fun g()".to_string(), vec![(2, 22), (3, 4)])
+        );
+        assert_eq!(
+            positions(4, 29),
+            ("A.m()".to_string(), vec![(4, 10), (4, 28)])
+        );
+    }
+}
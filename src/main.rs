@@ -1,60 +1,735 @@
-use std::io::{self, BufRead, Write};
+use std::{
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
-use jlox_rs::{self, error::LoxError, interpreter::Interpreter};
+use clap::{Parser, Subcommand, ValueEnum};
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+
+use jlox_rs::{
+    self,
+    coverage::Coverage,
+    debugger::{Debugger, Terminal},
+    error::LoxError,
+    formatter,
+    interpreter::{ExecutionMode, Interpreter},
+    limits,
+    lint::{self, Warning},
+    profiler::Profiler,
+    repl, test_runner,
+    tracer::Tracer,
+    vm::Vm,
+    Timings,
+};
+
+/// Runs a Lox script, or starts a prompt when there is none.
+#[derive(Debug, Parser)]
+#[command(
+    name = "jlox",
+    bin_name = "jlox",
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Run on the bytecode VM instead of the tree-walking interpreter
+    #[arg(long)]
+    vm: bool,
+    /// Compile the program into Rust closures before running it, instead of
+    /// walking the syntax tree
+    #[arg(long, conflicts_with = "vm")]
+    closure: bool,
+    /// Print how long each phase took to stderr
+    #[arg(long)]
+    bench: bool,
+    /// Print calls, total and self time per function to stderr, and write
+    /// the call stacks in flamegraph's folded format to FILE if given
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with = "vm"
+    )]
+    profile: Option<Option<PathBuf>>,
+    /// Write which lines and branches ran to FILE in lcov format, lcov.info
+    /// by default
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with = "vm"
+    )]
+    coverage: Option<Option<PathBuf>>,
+    /// Log each statement, variable definition and assignment, call and return to stderr
+    #[arg(long, conflicts_with = "vm")]
+    trace: bool,
+    /// Only trace calls to FUNCTION and what they do; can be repeated
+    #[arg(long, value_name = "FUNCTION", requires = "trace")]
+    trace_fn: Vec<String>,
+    /// Run the script in a step debugger that reads commands from stdin
+    #[arg(long, conflicts_with = "vm")]
+    debug: bool,
+    /// Print the tokens instead of running the program
+    #[arg(long)]
+    tokens: bool,
+    /// Print the syntax tree instead of running the program
+    #[arg(long)]
+    ast: bool,
+    /// Print the syntax tree as JSON instead of running the program
+    #[arg(long)]
+    ast_json: bool,
+    /// Print the syntax tree as a Graphviz graph instead of running the program
+    #[arg(long)]
+    dot_ast: bool,
+    /// Print a Graphviz graph of which functions refer to which instead of running the program
+    #[arg(long)]
+    dot_calls: bool,
+    /// Print the scope depth of each variable reference instead of running the program
+    #[arg(long)]
+    resolved: bool,
+    /// Stop after reporting static errors, without running the program
+    #[arg(long)]
+    check: bool,
+    /// Run CODE instead of a script
+    #[arg(short = 'e', value_name = "CODE", group = "source")]
+    eval: Option<String>,
+    /// Lox file to run; `-` reads the program from stdin
+    #[arg(group = "source")]
+    script: Option<PathBuf>,
+    /// Arguments for the script, read with `argc()` and `argv(i)`
+    #[arg(
+        requires = "script",
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Reformat Lox files in place, or stdin to stdout
+    Fmt {
+        /// Write nothing; list the files that are not formatted and fail if there are any
+        #[arg(long)]
+        check: bool,
+        files: Vec<PathBuf>,
+    },
+    /// Warn about code that runs but is probably wrong
+    Lint {
+        /// Rules to turn off, as {"rules": {"empty-block": false}}; defaults to jlox-lint.json if it exists
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+        /// Files to lint; stdin if there are none
+        files: Vec<PathBuf>,
+    },
+    /// Run the test_* functions in every *_test.lox file under the given paths
+    Test {
+        /// Test files, or directories to search; the current directory if there are none
+        paths: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Human,
+    Json,
+}
+
+impl Cli {
+    fn dumps(&self) -> bool {
+        self.tokens || self.ast || self.ast_json || self.dot_ast || self.dot_calls || self.resolved
+    }
+}
 
 fn main() -> io::Result<()> {
-    let mut args = std::env::args().skip(1);
+    limits::with_stack(run_cli)
+}
 
-    match args.len() {
-        0 => run_prompt()?,
-        1 => {
-            let path = args.next().unwrap();
-            run_file(&path)?;
+fn run_cli() -> io::Result<()> {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // `--help` is not a usage error.
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => {
+            let _ = err.print();
+            std::process::exit(64);
         }
-        _ => {
-            println!("Usage: jlox [script]");
+    };
+
+    match &cli.command {
+        Some(Command::Fmt { check, files }) => std::process::exit(run_fmt(files, *check)?),
+        Some(Command::Lint {
+            config,
+            format,
+            files,
+        }) => std::process::exit(run_lint(config.as_deref(), *format, files)?),
+        Some(Command::Test { paths }) => std::process::exit(run_test(paths)?),
+        None => (),
+    }
+
+    let mut backend = if cli.vm {
+        Backend::Vm(Vm::new())
+    } else {
+        Backend::TreeWalk(Box::new(Interpreter::new()))
+    };
+    if let (true, Backend::TreeWalk(interpreter)) = (cli.closure, &mut backend) {
+        interpreter.set_execution_mode(ExecutionMode::Closure);
+    }
+
+    // Debugger commands come from stdin, so the program cannot.
+    let from_stdin = cli
+        .script
+        .as_ref()
+        .is_none_or(|path| path.as_os_str() == "-");
+    if cli.debug && cli.eval.is_none() && from_stdin {
+        eprintln!("error: the program to debug must come from a script or -e");
+        std::process::exit(64);
+    }
+
+    // Without a script, a program piped in on stdin runs like a file.
+    let (source, name) = match (&cli.eval, &cli.script) {
+        (Some(code), _) => (code.clone(), "-e".to_string()),
+        (None, Some(path)) if path.as_os_str() == "-" => {
+            (io::read_to_string(io::stdin())?, "-".to_string())
+        }
+        (None, Some(path)) => (std::fs::read_to_string(path)?, path.display().to_string()),
+        (None, None) if !io::stdin().is_terminal() => {
+            (io::read_to_string(io::stdin())?, "-".to_string())
+        }
+        (None, None) if cli.dumps() || cli.check => {
+            eprintln!("error: the program to inspect must come from a script, -e or stdin");
             std::process::exit(64);
         }
+        (None, None) => return run_prompt(&mut backend).map_err(io::Error::other),
+    };
+
+    // `argv(0)` is the script, like in C.
+    let args = std::iter::once(name.clone())
+        .chain(cli.args.clone())
+        .collect();
+    backend.set_args(args);
+    if let (true, Backend::TreeWalk(interpreter)) = (cli.debug, &mut backend) {
+        interpreter.set_debugger(Debugger::new(Terminal::new(&source)));
+    }
+    if let (Some(_), Backend::TreeWalk(interpreter)) = (&cli.profile, &mut backend) {
+        interpreter.set_profiler(Profiler::new());
+    }
+    if let (true, Backend::TreeWalk(interpreter)) = (cli.trace, &mut backend) {
+        let mut tracer = Tracer::new(&source);
+        for function in &cli.trace_fn {
+            tracer.only(function);
+        }
+        interpreter.set_tracer(tracer);
+    }
+    if let (Some(_), Backend::TreeWalk(interpreter)) = (&cli.coverage, &mut backend) {
+        interpreter.set_coverage(Coverage::new());
+    }
+
+    run_source(&source, &name, &cli, &mut backend);
+
+    Ok(())
+}
+
+enum Backend {
+    TreeWalk(Box<Interpreter>),
+    Vm(Vm),
+}
+
+impl Backend {
+    #[allow(clippy::result_large_err)]
+    fn run(&mut self, source: &str, timings: &mut Timings) -> Result<(), LoxError> {
+        match self {
+            Backend::TreeWalk(interpreter) => jlox_rs::run_timed(source, interpreter, timings),
+            Backend::Vm(vm) => jlox_rs::run_vm_timed(source, vm, timings),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn run_repl(&mut self, source: &str) -> Result<(), LoxError> {
+        match self {
+            Backend::TreeWalk(interpreter) => jlox_rs::run_repl(source, interpreter),
+            Backend::Vm(vm) => jlox_rs::run_vm_repl(source, vm),
+        }
+    }
+
+    fn set_args(&mut self, args: Vec<String>) {
+        match self {
+            Backend::TreeWalk(interpreter) => interpreter.set_args(args),
+            Backend::Vm(vm) => vm.set_args(args),
+        }
+    }
+
+    fn global_names(&self) -> Vec<String> {
+        match self {
+            Backend::TreeWalk(interpreter) => interpreter.global_names(),
+            Backend::Vm(vm) => vm.global_names(),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn dump(source: &str, cli: &Cli) -> Result<(), LoxError> {
+    if cli.tokens {
+        for token in jlox_rs::dump_tokens(source)? {
+            println!("{}", token);
+        }
+    }
+    if cli.ast {
+        print!("{}", jlox_rs::dump_ast(source)?);
+    }
+    if cli.ast_json {
+        println!("{}", jlox_rs::dump_ast_json(source)?);
+    }
+    if cli.dot_ast {
+        print!("{}", jlox_rs::dump_dot_ast(source)?);
+    }
+    if cli.dot_calls {
+        print!("{}", jlox_rs::dump_dot_calls(source)?);
+    }
+    if cli.resolved {
+        for reference in jlox_rs::dump_resolved(source)? {
+            println!("{}", reference);
+        }
     }
 
     Ok(())
 }
 
-fn run_file(path: &str) -> io::Result<()> {
+/// Returns the exit status: 65 if a file has syntax errors, else 1 if
+/// `check` found files that are not formatted.
+fn run_fmt(files: &[PathBuf], check: bool) -> io::Result<i32> {
+    if files.is_empty() {
+        let source = io::read_to_string(io::stdin())?;
+        return Ok(match formatter::format(&source) {
+            Ok(formatted) if check => (formatted != source) as i32,
+            Ok(formatted) => {
+                print!("{}", formatted);
+                0
+            }
+            Err(_) => 65,
+        });
+    }
+
+    let mut status = 0;
+
+    for path in files {
+        let source = std::fs::read_to_string(path)?;
+        let formatted = match formatter::format(&source) {
+            Ok(formatted) => formatted,
+            Err(_) => {
+                eprintln!("Could not format {}.", path.display());
+                status = 65;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", path.display());
+            status = status.max(1);
+        } else {
+            std::fs::write(path, formatted)?;
+        }
+    }
+
+    Ok(status)
+}
+
+const LINT_CONFIG: &str = "jlox-lint.json";
+
+/// Returns the exit status: 65 if a file has static errors, else 1 if there
+/// were warnings.
+fn run_lint(config: Option<&Path>, format: Format, files: &[PathBuf]) -> io::Result<i32> {
+    let config_path = config.or_else(|| {
+        let default = Path::new(LINT_CONFIG);
+        default.exists().then_some(default)
+    });
+    let config = match config_path {
+        Some(path) => match lint::Config::from_json(&std::fs::read_to_string(path)?) {
+            Ok(config) => config,
+            Err(message) => {
+                eprintln!("{}: {}", path.display(), message);
+                // EX_CONFIG
+                return Ok(78);
+            }
+        },
+        None => lint::Config::default(),
+    };
+
+    let sources = if files.is_empty() {
+        vec![("-".to_string(), io::read_to_string(io::stdin())?)]
+    } else {
+        files
+            .iter()
+            .map(|path| Ok((path.display().to_string(), std::fs::read_to_string(path)?)))
+            .collect::<io::Result<_>>()?
+    };
+
+    let mut status = 0;
+    let mut reports: Vec<(String, Warning)> = vec![];
+
+    for (name, source) in sources {
+        match lint::lint(&source, &config) {
+            Ok(warnings) => reports.extend(warnings.into_iter().map(|w| (name.clone(), w))),
+            Err(_) => status = 65,
+        }
+    }
+    if !reports.is_empty() {
+        status = status.max(1);
+    }
+
+    match format {
+        Format::Human => {
+            for (file, warning) in &reports {
+                println!(
+                    "{}:{}: {} [{}]",
+                    file,
+                    warning.line,
+                    warning.message,
+                    warning.rule.name()
+                );
+            }
+        }
+        Format::Json => {
+            let json: Vec<_> = reports
+                .iter()
+                .map(|(file, warning)| {
+                    serde_json::json!({
+                        "file": file,
+                        "line": warning.line,
+                        "rule": warning.rule.name(),
+                        "message": warning.message,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+    }
+
+    Ok(status)
+}
+
+/// Returns the exit status: 65 if a file has static errors, else 1 if a test
+/// failed.
+fn run_test(paths: &[PathBuf]) -> io::Result<i32> {
+    let paths = match paths {
+        [] => &[PathBuf::from(".")][..],
+        paths => paths,
+    };
+    let mut files = vec![];
+    for path in paths {
+        files.extend(test_runner::discover(path)?);
+    }
+
+    let mut status = 0;
+    let mut passed = 0;
+    let mut failures = vec![];
+
+    for path in files {
+        let source = std::fs::read_to_string(&path)?;
+        let outcomes = match test_runner::run_tests(&source) {
+            Ok(outcomes) => outcomes,
+            Err(_) => {
+                println!("{} ... could not compile", path.display());
+                status = 65;
+                continue;
+            }
+        };
+
+        for outcome in outcomes {
+            let test = format!("{}::{}", path.display(), outcome.name);
+            match outcome.failure {
+                None => {
+                    println!("test {} ... ok", test);
+                    passed += 1;
+                }
+                Some(failure) => {
+                    println!("test {} ... FAILED", test);
+                    failures.push((test, failure));
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (test, failure) in &failures {
+            println!("    {}: {}", test, failure);
+        }
+        status = status.max(1);
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if status == 0 { "ok" } else { "FAILED" },
+        passed,
+        failures.len()
+    );
+
+    Ok(status)
+}
+
+fn run_source(source: &str, name: &str, cli: &Cli, backend: &mut Backend) {
     use LoxError::*;
 
-    let source = std::fs::read_to_string(path)?;
-    let mut interpreter = Interpreter::new();
+    let result = if cli.dumps() {
+        dump(source, cli)
+    } else if cli.check {
+        jlox_rs::check(source)
+    } else {
+        let mut timings = Timings::default();
+        let result = backend.run(source, &mut timings);
+
+        if cli.bench {
+            report_timings(&timings, matches!(backend, Backend::Vm(_)));
+        }
+        if let Backend::TreeWalk(interpreter) = backend {
+            if let (Some(folded), Some(profiler)) = (&cli.profile, interpreter.take_profiler()) {
+                report_profile(&profiler, folded.as_deref());
+            }
+            if let (Some(path), Some(coverage)) = (&cli.coverage, interpreter.take_coverage()) {
+                write_coverage(&coverage, name, path.as_deref());
+            }
+        }
+        result
+    };
 
-    match jlox_rs::run(&source, &mut interpreter) {
-        Err(ScanError | ParseError) => std::process::exit(65),
-        Err(RuntimeError(..)) => std::process::exit(70),
+    match result {
+        Err(ScanError | ParseError | ResolveError | CompileError) => std::process::exit(65),
+        Err(RuntimeError(..) | AssertionFailed(..) | CallDepthExceeded(_)) => {
+            std::process::exit(70)
+        }
+        Err(Exit(code)) => std::process::exit(code),
+        Err(
+            ref limit @ (StepLimitExceeded
+            | TimeLimitExceeded
+            | AllocationLimitExceeded
+            | OutputLimitExceeded),
+        ) => {
+            eprintln!("{}", limit_message(limit));
+            std::process::exit(70)
+        }
         _ => (),
     }
+}
 
-    Ok(())
+/// What `run_source` reports for a sandbox limit, which the interpreter
+/// leaves to its host.
+fn limit_message(limit: &LoxError) -> &'static str {
+    match limit {
+        LoxError::StepLimitExceeded => "Step limit exceeded.",
+        LoxError::TimeLimitExceeded => "Time limit exceeded.",
+        LoxError::AllocationLimitExceeded => "Allocation limit exceeded.",
+        LoxError::OutputLimitExceeded => "Output limit exceeded.",
+        _ => unreachable!(),
+    }
+}
+
+/// Tab completion for the prompt. `names` is refreshed from the backend's
+/// globals before every line is read.
+#[derive(Default)]
+struct LoxHelper {
+    names: Vec<String>,
+}
+
+impl Completer for LoxHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(repl::complete(line, pos, &self.names))
+    }
 }
 
-fn run_prompt() -> io::Result<()> {
-    let mut buf;
-    let mut stdin = io::stdin().lock();
-    let mut stdout = io::stdout();
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper {}
+
+impl Validator for LoxHelper {}
 
-    let mut interpreter = Interpreter::new();
+impl Helper for LoxHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::home_dir().map(|home| home.join(".jlox_history"))
+}
+
+fn run_prompt(backend: &mut Backend) -> rustyline::Result<()> {
+    let mut editor = Editor::<LoxHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(LoxHelper::default()));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        // There is no history yet on the first run.
+        let _ = editor.load_history(path);
+    }
+
+    let mut buf = String::new();
+    // Set when the code calls `exit`.
+    let mut status = 0;
 
     loop {
-        print!("> ");
-        stdout.flush()?;
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = backend.global_names();
+        }
 
-        buf = String::new();
-        stdin.read_line(&mut buf)?;
+        // Keep reading while the entry so far has open brackets or strings.
+        let prompt = if buf.is_empty() { "> " } else { "... " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if buf.is_empty() {
+                    if let Some(command) = repl::Command::parse(&line) {
+                        editor.add_history_entry(line.trim())?;
+                        match command {
+                            Ok(command) => {
+                                if let Err(LoxError::Exit(code)) = run_command(command, backend) {
+                                    status = code;
+                                    break;
+                                }
+                            }
+                            Err(message) => eprintln!("{}", message),
+                        }
+                        continue;
+                    }
+                }
+
+                buf.push_str(&line);
+                buf.push('\n');
+            }
+            // Ctrl-C drops the entry being typed but keeps the session.
+            Err(ReadlineError::Interrupted) => {
+                buf.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err),
+        }
+
+        if repl::is_incomplete(&buf) {
+            continue;
+        }
+
+        let entry = buf.trim_end();
+        if !entry.is_empty() {
+            editor.add_history_entry(entry)?;
+        }
 
-        if buf.is_empty() {
+        if let Err(LoxError::Exit(code)) = backend.run_repl(&buf) {
+            status = code;
             break;
         }
+        buf.clear();
+    }
+
+    // Losing the history is not worth failing the session over.
+    if let Some(path) = &history {
+        let _ = editor.save_history(path);
+    }
+
+    if status != 0 {
+        std::process::exit(status);
+    }
+
+    Ok(())
+}
+
+// Errors in the code given to a command are reported before they get here;
+// the caller only has to act on `exit`.
+#[allow(clippy::result_large_err)]
+fn run_command(command: repl::Command, backend: &mut Backend) -> Result<(), LoxError> {
+    use repl::Command::*;
 
-        let _ = jlox_rs::run(&buf, &mut interpreter);
+    match command {
+        Help => println!("{}", repl::HELP),
+        Env => {
+            let globals = match backend {
+                Backend::TreeWalk(interpreter) => interpreter.global_values(),
+                Backend::Vm(vm) => vm.global_values(),
+            };
+            for (name, value) in globals {
+                println!("{} = {}", name, value);
+            }
+        }
+        Type(source) => {
+            let type_name = match backend {
+                Backend::TreeWalk(interpreter) => repl::type_of(source, interpreter)?,
+                Backend::Vm(vm) => repl::vm_type_of(source, vm)?,
+            };
+            println!("{}", type_name);
+        }
+        Ast(source) => println!("{}", repl::ast(source)?),
+        Tokens(source) => {
+            for token in jlox_rs::dump_tokens(source)? {
+                println!("{}", token);
+            }
+        }
+        Load(path) => match std::fs::read_to_string(path) {
+            Ok(source) => backend.run(&source, &mut Timings::default())?,
+            Err(err) => eprintln!("Could not read '{}': {}", path, err),
+        },
+        Reset => {
+            *backend = match backend {
+                Backend::TreeWalk(_) => Backend::TreeWalk(Box::new(Interpreter::new())),
+                Backend::Vm(_) => Backend::Vm(Vm::new()),
+            };
+        }
+        Time(source) => {
+            let start = Instant::now();
+            let result = backend.run_repl(source);
+            println!("{:.3} ms", start.elapsed().as_secs_f64() * 1000.0);
+            result?
+        }
     }
 
     Ok(())
 }
+
+fn report_timings(timings: &Timings, compiled: bool) {
+    let mut phases = vec![
+        ("scan", timings.scan),
+        ("parse", timings.parse),
+        ("resolve", timings.resolve),
+        ("optimize", timings.optimize),
+    ];
+    if compiled {
+        phases.push(("compile", timings.compile));
+    }
+    phases.push(("execute", timings.execute));
+    phases.push(("total", timings.total()));
+
+    for (phase, duration) in phases {
+        eprintln!("{:<10}{:>12.3} ms", phase, duration.as_secs_f64() * 1000.0);
+    }
+}
+
+fn report_profile(profiler: &Profiler, folded: Option<&Path>) {
+    eprint!("{}", profiler.report());
+    if let Some(path) = folded {
+        if let Err(err) = std::fs::write(path, profiler.folded()) {
+            eprintln!("Could not write '{}': {}", path.display(), err);
+        }
+    }
+}
+
+const LCOV: &str = "lcov.info";
+
+fn write_coverage(coverage: &Coverage, name: &str, path: Option<&Path>) {
+    let path = path.unwrap_or(Path::new(LCOV));
+    if let Err(err) = std::fs::write(path, coverage.to_lcov(name)) {
+        eprintln!("Could not write '{}': {}", path.display(), err);
+    }
+}
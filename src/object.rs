@@ -40,6 +40,20 @@ impl Object {
         }
         true
     }
+
+    /// The name of the value's type as the REPL's `:type` shows it.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Bool(_) => "boolean",
+            Object::Num(_) => "number",
+            Object::Str(_) => "string",
+            Object::Null => "nil",
+            Object::Callable(CallableKind::Function(_)) => "function",
+            Object::Callable(CallableKind::Native(_)) => "native function",
+            Object::Callable(CallableKind::Class(_)) => "class",
+            Object::Instance(_) => "instance",
+        }
+    }
 }
 
 impl std::fmt::Display for Object {
@@ -135,6 +149,8 @@ impl std::cmp::PartialEq for Object {
             (Object::Bool(a), Object::Bool(b)) => a.eq(b),
             (Object::Str(a), Object::Str(b)) => a.eq(b),
             (Object::Null, Object::Null) => true,
+            (Object::Callable(a), Object::Callable(b)) => a == b,
+            (Object::Instance(a), Object::Instance(b)) => a == b,
             _ => false,
         }
     }
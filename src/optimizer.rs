@@ -0,0 +1,276 @@
+//! Constant folding over a resolved program.
+//!
+//! The pass consumes the statements and rebuilds only what it simplifies, so
+//! every variable, assignment, `this` and `super` node keeps the id the
+//! `Resolver` stored its depth under. Operations that would fail at runtime
+//! (`1 + "a"`, `-"a"`, `1 < nil`) are left alone so they still fail there,
+//! on the same line.
+
+use std::rc::Rc;
+
+use crate::{expr::*, object::Object, stmt::*, token_type::TokenType};
+
+pub fn optimize(statements: Vec<Stmt>) -> Vec<Stmt> {
+    statements.into_iter().filter_map(fold_stmt).collect()
+}
+
+fn fold_stmt(stmt: Stmt) -> Option<Stmt> {
+    match stmt {
+        Stmt::Expression(mut stmt) => {
+            stmt.expression = fold_expr(stmt.expression);
+            Some(Stmt::Expression(stmt))
+        }
+        Stmt::Print(mut stmt) => {
+            stmt.expression = fold_expr(stmt.expression);
+            Some(Stmt::Print(stmt))
+        }
+        Stmt::Var(mut stmt) => {
+            stmt.initializer = stmt.initializer.map(fold_expr);
+            Some(Stmt::Var(stmt))
+        }
+        Stmt::Block(mut stmt) => {
+            stmt.statements = optimize(stmt.statements);
+            Some(Stmt::Block(stmt))
+        }
+        Stmt::If(stmt) => {
+            let condition = fold_expr(stmt.condition);
+
+            if let Some(truthy) = literal_truthiness(&condition) {
+                return if truthy {
+                    fold_stmt(*stmt.then_branch)
+                } else {
+                    stmt.else_branch.and_then(|branch| fold_stmt(*branch))
+                };
+            }
+
+            Some(Stmt::new_if(
+                stmt.keyword,
+                condition,
+                Box::new(fold_body(*stmt.then_branch)),
+                stmt.else_branch
+                    .and_then(|branch| fold_stmt(*branch))
+                    .map(Box::new),
+            ))
+        }
+        Stmt::While(stmt) => {
+            let condition = fold_expr(stmt.condition);
+
+            if literal_truthiness(&condition) == Some(false) {
+                return None;
+            }
+
+            Some(Stmt::new_while(
+                stmt.keyword,
+                condition,
+                Box::new(fold_body(*stmt.body)),
+            ))
+        }
+        Stmt::Function(mut stmt) => {
+            let function = Rc::make_mut(&mut stmt);
+            function.body = optimize(std::mem::take(&mut function.body));
+            Some(Stmt::Function(stmt))
+        }
+        Stmt::Return(mut stmt) => {
+            stmt.value = stmt.value.map(fold_expr);
+            Some(Stmt::Return(stmt))
+        }
+        Stmt::Class(mut stmt) => {
+            stmt.methods = optimize(stmt.methods);
+            Some(Stmt::Class(stmt))
+        }
+    }
+}
+
+// The body of an `if` or `while` must stay a statement even if it folds away.
+fn fold_body(stmt: Stmt) -> Stmt {
+    fold_stmt(stmt).unwrap_or_else(|| Stmt::new_block(vec![]))
+}
+
+fn fold_expr(expr: Expr) -> Expr {
+    match expr {
+        Expr::Literal(_) | Expr::Variable(_) | Expr::This(_) | Expr::Super(_) => expr,
+        Expr::Unary(mut expr) => {
+            let right = fold_expr(*expr.right);
+
+            if let Expr::Literal(ref literal) = right {
+                match (expr.operator.typ, &literal.value) {
+                    (TokenType::Bang, value) => {
+                        let line = Some(expr.operator.line);
+                        return Expr::new_literal(Object::Bool(!value.is_truthy()), line);
+                    }
+                    (TokenType::Minus, Object::Num(value)) => {
+                        return Expr::new_literal(Object::Num(-value), Some(expr.operator.line))
+                    }
+                    _ => (),
+                }
+            }
+
+            expr.right = Box::new(right);
+            Expr::Unary(expr)
+        }
+        Expr::Binary(mut expr) => {
+            let left = fold_expr(*expr.left);
+            let right = fold_expr(*expr.right);
+
+            if let (Expr::Literal(ref left), Expr::Literal(ref right)) = (&left, &right) {
+                if let Some(value) = fold_binary(expr.operator.typ, &left.value, &right.value) {
+                    let line = left.line.or(Some(expr.operator.line));
+                    return Expr::new_literal(value, line);
+                }
+            }
+
+            expr.left = Box::new(left);
+            expr.right = Box::new(right);
+            Expr::Binary(expr)
+        }
+        Expr::Grouping(mut expr) => {
+            let expression = fold_expr(*expr.expression);
+
+            if let Expr::Literal(_) = expression {
+                return expression;
+            }
+
+            expr.expression = Box::new(expression);
+            Expr::Grouping(expr)
+        }
+        Expr::Assign(mut expr) => {
+            expr.value = Box::new(fold_expr(*expr.value));
+            Expr::Assign(expr)
+        }
+        Expr::Logical(mut expr) => {
+            let left = fold_expr(*expr.left);
+            let right = fold_expr(*expr.right);
+
+            if let Some(truthy) = literal_truthiness(&left) {
+                // `or` keeps a truthy left operand, `and` keeps a falsey one.
+                let keep_left = truthy == (expr.operator.typ == TokenType::Or);
+                return if keep_left { left } else { right };
+            }
+
+            expr.left = Box::new(left);
+            expr.right = Box::new(right);
+            Expr::Logical(expr)
+        }
+        Expr::Call(mut expr) => {
+            expr.callee = Box::new(fold_expr(*expr.callee));
+            expr.arguments = expr.arguments.into_iter().map(fold_expr).collect();
+            Expr::Call(expr)
+        }
+        Expr::Get(mut expr) => {
+            expr.object = Box::new(fold_expr(*expr.object));
+            Expr::Get(expr)
+        }
+        Expr::Set(mut expr) => {
+            expr.object = Box::new(fold_expr(*expr.object));
+            expr.value = Box::new(fold_expr(*expr.value));
+            Expr::Set(expr)
+        }
+    }
+}
+
+fn literal_truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal(literal) => Some(literal.value.is_truthy()),
+        _ => None,
+    }
+}
+
+// Returns `None` whenever the interpreter would raise a runtime error.
+fn fold_binary(operator: TokenType, left: &Object, right: &Object) -> Option<Object> {
+    use TokenType::*;
+
+    match (operator, left, right) {
+        (EqualEqual, left, right) => Some(Object::Bool(left == right)),
+        (BangEqual, left, right) => Some(Object::Bool(left != right)),
+        (Plus, Object::Str(a), Object::Str(b)) => Some(Object::Str(format!("{}{}", a, b))),
+        (_, Object::Num(a), Object::Num(b)) => match operator {
+            Plus => Some(Object::Num(a + b)),
+            Minus => Some(Object::Num(a - b)),
+            Star => Some(Object::Num(a * b)),
+            Slash => Some(Object::Num(a / b)),
+            Greater => Some(Object::Bool(a > b)),
+            GreaterEqual => Some(Object::Bool(a >= b)),
+            Less => Some(Object::Bool(a < b)),
+            LessEqual => Some(Object::Bool(a <= b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ast_printer::AstPrinter, error::LoxError, interpreter::Interpreter, parser::Parser,
+        resolver::Resolver, scanner::Scanner,
+    };
+
+    use super::*;
+
+    fn fold(source: &str) -> String {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse_one_expr().unwrap();
+        AstPrinter.print(&fold_expr(expr))
+    }
+
+    fn optimize_source(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        optimize(Parser::new(tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn folds_constant_expressions() {
+        assert_eq!(fold("60 * 60 * 24"), "86400");
+        assert_eq!(fold("\"a\" + \"b\""), "ab");
+        assert_eq!(fold("(1 + 2) * 3 >= 9"), "true");
+        assert_eq!(fold("!nil == !false"), "true");
+        assert_eq!(fold("-(2 - 5)"), "3");
+        assert_eq!(fold("nil or \"x\""), "x");
+        assert_eq!(fold("0 and \"x\""), "x");
+        assert_eq!(fold("false and 1 + \"a\""), "false");
+    }
+
+    #[test]
+    fn leaves_failing_operations_for_runtime() {
+        assert_eq!(fold("1 + \"a\""), "(+ 1 a)");
+        assert_eq!(fold("-\"a\""), "(- a)");
+        assert_eq!(fold("(2 * 3) < nil"), "(< 6 nil)");
+    }
+
+    #[test]
+    fn removes_dead_branches_and_loops() {
+        let statements = optimize_source("if (false) print 1; while (nil) print 2;");
+        assert!(statements.is_empty());
+
+        let statements = optimize_source("if (1 == 1) print 1; else print 2;");
+        assert!(matches!(statements.as_slice(), [Stmt::Print(_)]));
+
+        let statements = optimize_source("while (true) if (false) print 1;");
+        match statements.as_slice() {
+            [Stmt::While(stmt)] => {
+                assert!(matches!(*stmt.body, Stmt::Block(ref block) if block.statements.is_empty()))
+            }
+            _ => panic!("expected a single while loop"),
+        }
+    }
+
+    #[test]
+    fn keeps_runtime_errors_and_resolved_variables() {
+        let source = "var a = 1;\n{\n  var b = 2 * 3;\n  a = b + 60 * 60;\n}\nprint a +\n  \"s\";";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter)
+            .resolve(&statements)
+            .unwrap();
+
+        match interpreter.interpret(&optimize(statements)) {
+            Err(LoxError::RuntimeError(token, message)) => {
+                assert_eq!(token.line, 6);
+                assert_eq!(message, "Operands must be two numbers or two strings.");
+            }
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+}
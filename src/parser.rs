@@ -14,6 +14,7 @@ pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
     had_error: Cell<bool>,
+    repl: bool,
 }
 
 impl Parser {
@@ -22,9 +23,17 @@ impl Parser {
             tokens,
             current: 0,
             had_error: Cell::new(false),
+            repl: false,
         }
     }
 
+    /// Like `parse`, but a final expression with no `;` becomes a `print`
+    /// statement, so the REPL can show the value of a bare expression.
+    pub fn parse_repl(&mut self) -> Result<Vec<Stmt>, LoxError> {
+        self.repl = true;
+        self.parse()
+    }
+
     pub fn parse(&mut self) -> Result<Vec<Stmt>, LoxError> {
         let mut statements = Vec::new();
 
@@ -45,6 +54,17 @@ impl Parser {
         Ok(statements)
     }
 
+    /// Parses a single expression that must make up the whole input.
+    pub(crate) fn parse_expression(&mut self) -> Result<Expr, LoxError> {
+        let expr = self.expression()?;
+
+        if !self.is_at_end() {
+            return Err(self.error(self.peek(), "Expect end of expression."));
+        }
+
+        Ok(expr)
+    }
+
     // before 8.1.2 Parsing statements
     #[allow(dead_code)]
     pub(crate) fn parse_one_expr(&mut self) -> Result<Expr, LoxError> {
@@ -100,6 +120,7 @@ impl Parser {
     }
 
     fn for_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
         let initializer;
@@ -130,9 +151,9 @@ impl Parser {
         }
 
         if condition.is_none() {
-            condition = Some(Expr::new_literal(Object::Bool(true)));
+            condition = Some(Expr::new_literal(Object::Bool(true), None));
         }
-        body = Stmt::new_while(condition.unwrap(), Box::new(body));
+        body = Stmt::new_while(keyword, condition.unwrap(), Box::new(body));
 
         if let Some(initializer) = initializer {
             body = Stmt::new_block(vec![initializer, body]);
@@ -142,6 +163,7 @@ impl Parser {
     }
 
     fn if_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
@@ -152,13 +174,14 @@ impl Parser {
             else_branch = Some(Box::new(self.statement()?));
         }
 
-        Ok(Stmt::new_if(condition, then_branch, else_branch))
+        Ok(Stmt::new_if(keyword, condition, then_branch, else_branch))
     }
 
     fn print_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
         let value = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::new_print(value))
+        Ok(Stmt::new_print(keyword, value))
     }
 
     fn return_statement(&mut self) -> Result<Stmt, LoxError> {
@@ -175,12 +198,13 @@ impl Parser {
     }
 
     fn while_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
         let body = Box::new(self.statement()?);
 
-        Ok(Stmt::new_while(condition, body))
+        Ok(Stmt::new_while(keyword, condition, body))
     }
 
     fn function(&mut self, kind: &str) -> Result<Stmt, LoxError> {
@@ -270,6 +294,11 @@ impl Parser {
 
     fn expression_statement(&mut self) -> Result<Stmt, LoxError> {
         let expr = self.expression()?;
+        if self.repl && self.is_at_end() {
+            let line = self.previous().line;
+            let keyword = Token::new(TokenType::Print, "print".to_string(), Object::Null, line);
+            return Ok(Stmt::new_print(keyword, expr));
+        }
         self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
         Ok(Stmt::new_expression(expr))
     }
@@ -454,17 +483,24 @@ impl Parser {
         use TokenType::*;
 
         if self.match_tokentype(&[False]) {
-            return Ok(Expr::new_literal(Object::Bool(false)));
+            return Ok(Expr::new_literal(
+                Object::Bool(false),
+                Some(self.previous().line),
+            ));
         }
         if self.match_tokentype(&[True]) {
-            return Ok(Expr::new_literal(Object::Bool(true)));
+            return Ok(Expr::new_literal(
+                Object::Bool(true),
+                Some(self.previous().line),
+            ));
         }
         if self.match_tokentype(&[Nil]) {
-            return Ok(Expr::new_literal(Object::Null));
+            return Ok(Expr::new_literal(Object::Null, Some(self.previous().line)));
         }
 
         if self.match_tokentype(&[Number, String]) {
-            return Ok(Expr::new_literal(self.previous().clone().literal));
+            let token = self.previous();
+            return Ok(Expr::new_literal(token.literal.clone(), Some(token.line)));
         }
 
         if self.match_tokentype(&[TokenType::Super]) {
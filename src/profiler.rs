@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+/// The name of the outermost frame, which runs the top-level statements.
+pub const SCRIPT: &str = "<script>";
+
+/// What the profiler measured for one function, native or class.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub calls: u64,
+    /// Time from call to return, counted once for recursive calls.
+    pub inclusive: Duration,
+    /// Inclusive time minus the time spent in the callables it called.
+    pub exclusive: Duration,
+}
+
+#[derive(Debug)]
+struct Call {
+    name: String,
+    start: Instant,
+    /// Inclusive time of the calls made from this one so far.
+    children: Duration,
+}
+
+/// Times every call the interpreter makes. Functions are keyed by name, so
+/// methods of different classes with the same name are counted together.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    stack: Vec<Call>,
+    stats: HashMap<String, Stats>,
+    /// Exclusive time of each call stack, joined with `;`.
+    stacks: HashMap<String, Duration>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn enter(&mut self, name: &str) {
+        self.stack.push(Call {
+            name: name.to_string(),
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    pub(crate) fn leave(&mut self) {
+        let key = self
+            .stack
+            .iter()
+            .map(|call| call.name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let call = self.stack.pop().expect("left a call that was not entered");
+        let inclusive = call.start.elapsed();
+        let exclusive = inclusive.saturating_sub(call.children);
+
+        if let Some(caller) = self.stack.last_mut() {
+            caller.children += inclusive;
+        }
+        *self.stacks.entry(key).or_default() += exclusive;
+
+        let recursive = self.stack.iter().any(|caller| caller.name == call.name);
+        let stats = self.stats.entry(call.name).or_default();
+        stats.calls += 1;
+        stats.exclusive += exclusive;
+        // The outermost call already covers the time of the recursive ones.
+        if !recursive {
+            stats.inclusive += inclusive;
+        }
+    }
+
+    /// Every callable that was called, most exclusive time first.
+    pub fn stats(&self) -> Vec<(&str, Stats)> {
+        let mut stats: Vec<_> = self
+            .stats
+            .iter()
+            .map(|(name, stats)| (name.as_str(), *stats))
+            .collect();
+        stats.sort_by(|(a_name, a), (b_name, b)| {
+            b.exclusive.cmp(&a.exclusive).then(a_name.cmp(b_name))
+        });
+        stats
+    }
+
+    /// A table of `stats` for people.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "{:<20}{:>10}{:>14}{:>14}\n",
+            "function", "calls", "total ms", "self ms"
+        );
+        for (name, stats) in self.stats() {
+            let _ = writeln!(
+                report,
+                "{:<20}{:>10}{:>14.3}{:>14.3}",
+                name,
+                stats.calls,
+                stats.inclusive.as_secs_f64() * 1000.0,
+                stats.exclusive.as_secs_f64() * 1000.0
+            );
+        }
+        report
+    }
+
+    /// Exclusive time per call stack in microseconds, one `a;b;c 123` line
+    /// per stack, as flamegraph.pl and inferno expect.
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+
+        let mut folded = String::new();
+        for (stack, time) in stacks {
+            let _ = writeln!(folded, "{} {}", stack, time.as_micros());
+        }
+        folded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_calls_and_recursion_once() {
+        let mut profiler = Profiler::new();
+        profiler.enter(SCRIPT);
+        profiler.enter("fib");
+        profiler.enter("fib");
+        profiler.leave();
+        profiler.enter("clock");
+        profiler.leave();
+        profiler.leave();
+        profiler.leave();
+
+        let stats: HashMap<_, _> = profiler.stats().into_iter().collect();
+        assert_eq!(stats["fib"].calls, 2);
+        assert_eq!(stats["clock"].calls, 1);
+        assert!(stats["fib"].inclusive <= stats[SCRIPT].inclusive);
+        assert!(stats["fib"].exclusive <= stats["fib"].inclusive);
+
+        let stacks: Vec<_> = profiler
+            .folded()
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0.to_string())
+            .collect();
+        assert_eq!(
+            stacks,
+            [
+                "<script>",
+                "<script>;fib",
+                "<script>;fib;clock",
+                "<script>;fib;fib"
+            ]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Helpers for the interactive prompt.
+
+use crate::{
+    ast_printer::AstPrinter,
+    error::{self, LoxError},
+    expr::Expr,
+    interpreter::Interpreter,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::{self, Scanner},
+    stmt::Stmt,
+    vm::{Compiler, Vm},
+};
+
+pub const HELP: &str = "\
+:help          show this list
+:env           list globals and their values
+:type <expr>   show the type of an expression's value
+:ast <expr>    show the syntax tree of an expression
+:tokens <code> show the tokens the scanner produces
+:load <file>   run a script in this session
+:reset         forget everything defined so far
+:time <code>   run code and show how long it took";
+
+/// A meta-command typed at the prompt, borrowing its argument from the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command<'a> {
+    Help,
+    Env,
+    Type(&'a str),
+    Ast(&'a str),
+    Tokens(&'a str),
+    Load(&'a str),
+    Reset,
+    Time(&'a str),
+}
+
+impl<'a> Command<'a> {
+    /// Parses `line` if it is a meta-command, i.e. starts with `:`. The error
+    /// is a message for the user.
+    pub fn parse(line: &'a str) -> Option<Result<Self, String>> {
+        let line = line.trim().strip_prefix(':')?;
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (line, ""),
+        };
+
+        let command = match name {
+            "help" => Command::Help,
+            "env" => Command::Env,
+            "reset" => Command::Reset,
+            "type" | "ast" | "tokens" | "load" | "time" if argument.is_empty() => {
+                return Some(Err(format!("Usage: :{} <argument>", name)));
+            }
+            "type" => Command::Type(argument),
+            "ast" => Command::Ast(argument),
+            "tokens" => Command::Tokens(argument),
+            "load" => Command::Load(argument),
+            "time" => Command::Time(argument),
+            _ => {
+                return Some(Err(format!(
+                    "Unknown command ':{}'. Type :help for a list.",
+                    name
+                )))
+            }
+        };
+
+        Some(Ok(command))
+    }
+}
+
+fn parse_expression(source: &str) -> Result<Expr, LoxError> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    Parser::new(tokens).parse_expression()
+}
+
+// Static errors such as `this` outside a class are reported before anything runs.
+fn resolve_expression(expr: &Expr, interpreter: &mut Interpreter) -> Result<(), LoxError> {
+    Resolver::new(interpreter).resolve(&[Stmt::new_expression(expr.clone())])
+}
+
+/// The parenthesized syntax tree of the expression `source`.
+pub fn ast(source: &str) -> Result<String, LoxError> {
+    Ok(AstPrinter.print(&parse_expression(source)?))
+}
+
+/// Evaluates the expression `source` and names the type of its value.
+pub fn type_of(source: &str, interpreter: &mut Interpreter) -> Result<&'static str, LoxError> {
+    let expr = parse_expression(source)?;
+    resolve_expression(&expr, interpreter)?;
+
+    match interpreter.evaluate(&expr) {
+        Ok(value) => Ok(value.type_name()),
+        Err(err) => {
+            match err {
+                LoxError::RuntimeError(ref token, ref message) => {
+                    error::lox_runtime_error(token, message)
+                }
+                LoxError::AssertionFailed(line, ref message) => {
+                    error::lox_assertion_failed(line, message)
+                }
+                LoxError::CallDepthExceeded(line) => error::lox_stack_overflow(line),
+                _ => (),
+            }
+            Err(err)
+        }
+    }
+}
+
+/// The `--vm` counterpart of `type_of`.
+pub fn vm_type_of(source: &str, vm: &mut Vm) -> Result<&'static str, LoxError> {
+    let expr = parse_expression(source)?;
+    resolve_expression(&expr, &mut Interpreter::new())?;
+
+    let function = Compiler::new().compile_expression(&expr)?;
+    Ok(vm.evaluate(function)?.type_name())
+}
+
+/// Returns `true` if `source` is unfinished and the prompt should read another
+/// line before running it: a string is still open, or there are more `(` or
+/// `{` than closing ones. Surplus closing brackets are left for the parser to
+/// report.
+pub fn is_incomplete(source: &str) -> bool {
+    let mut chars = source.chars().peekable();
+    let mut parens = 0i32;
+    let mut braces = 0i32;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if !chars.any(|c| c == '"') => return true,
+            '/' if chars.peek() == Some(&'/') => {
+                chars.find(|&c| c == '\n');
+            }
+            '(' => parens += 1,
+            ')' => parens -= 1,
+            '{' => braces += 1,
+            '}' => braces -= 1,
+            _ => (),
+        }
+    }
+
+    parens > 0 || braces > 0
+}
+
+/// Completes the identifier that ends at byte offset `pos` in `line` against
+/// the Lox keywords and the given `names`. Returns where the identifier starts
+/// and the sorted candidates; nothing is offered for an empty prefix.
+pub fn complete(line: &str, pos: usize, names: &[String]) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map_or(0, |index| index + 1);
+    let prefix = &line[start..pos];
+
+    if prefix.is_empty() {
+        return (start, vec![]);
+    }
+
+    let mut candidates: Vec<String> = scanner::keywords()
+        .map(String::from)
+        .chain(names.iter().cloned())
+        .filter(|candidate| candidate.starts_with(prefix))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+
+    (start, candidates)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_meta_commands() {
+        assert_eq!(Command::parse("print 1;"), None);
+        assert_eq!(Command::parse(" :env "), Some(Ok(Command::Env)));
+        assert_eq!(
+            Command::parse(":type  1 + 2"),
+            Some(Ok(Command::Type("1 + 2")))
+        );
+        assert_eq!(
+            Command::parse(":load examples/10.4.lox"),
+            Some(Ok(Command::Load("examples/10.4.lox")))
+        );
+        assert!(matches!(Command::parse(":ast"), Some(Err(_))));
+        assert!(matches!(Command::parse(":quit"), Some(Err(_))));
+    }
+
+    #[test]
+    fn inspects_expressions() {
+        let mut interpreter = Interpreter::new();
+        let mut vm = Vm::new();
+        crate::run("class A {} var a = A();", &mut interpreter).unwrap();
+        crate::run_vm("class A {} var a = A();", &mut vm).unwrap();
+
+        for (source, expected) in [
+            ("1 + 2", "number"),
+            ("\"a\" + \"b\"", "string"),
+            ("clock", "native function"),
+            ("A", "class"),
+            ("a", "instance"),
+            ("nil == nil", "boolean"),
+        ] {
+            assert_eq!(type_of(source, &mut interpreter).unwrap(), expected);
+            assert_eq!(vm_type_of(source, &mut vm).unwrap(), expected);
+        }
+
+        assert_eq!(
+            ast("-a.b(1, c = 2)").unwrap(),
+            "(- (call (. b a) 1 (= c 2)))"
+        );
+    }
+
+    #[test]
+    fn detects_unfinished_input() {
+        assert!(is_incomplete("fun f() {"));
+        assert!(is_incomplete("print (1 +\n"));
+        assert!(is_incomplete("var s = \"abc\n"));
+        assert!(is_incomplete("class A { init() { }\n"));
+
+        assert!(!is_incomplete("1 + 2"));
+        assert!(!is_incomplete("fun f() { return \"}\"; }"));
+        assert!(!is_incomplete("print 1; // {\n"));
+        assert!(!is_incomplete("}"));
+    }
+
+    #[test]
+    fn completes_keywords_and_globals() {
+        let names = vec!["clock".to_string(), "counter".to_string()];
+
+        assert_eq!(
+            complete("print co", 8, &names),
+            (6, vec!["counter".to_string()])
+        );
+        assert_eq!(
+            complete("c", 1, &names),
+            (0, vec!["class".into(), "clock".into(), "counter".into()])
+        );
+        assert_eq!(complete("wh(x", 2, &names), (0, vec!["while".to_string()]));
+        assert_eq!(complete("f(", 2, &names), (2, vec![]));
+    }
+}
@@ -0,0 +1,152 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{expr::*, stmt::*, token::Token};
+
+/// Lists every variable reference of a resolved program in source order,
+/// with the scope depth the `Resolver` assigned to it. References the
+/// resolver left alone are globals.
+#[derive(Debug)]
+pub struct ResolutionPrinter<'a> {
+    locals: &'a HashMap<Expr, usize>,
+    lines: Vec<String>,
+}
+
+impl<'a> ResolutionPrinter<'a> {
+    pub fn new(locals: &'a HashMap<Expr, usize>) -> Self {
+        Self {
+            locals,
+            lines: vec![],
+        }
+    }
+
+    pub fn print(mut self, statements: &[Stmt]) -> Vec<String> {
+        self.stmts(statements);
+        self.lines
+    }
+
+    fn reference(&mut self, expr: &Expr, name: &Token) {
+        let resolution = match self.locals.get(expr) {
+            Some(depth) => format!("local, depth {}", depth),
+            None => "global".to_string(),
+        };
+        self.lines.push(format!(
+            "[line {}] {}: {}",
+            name.line, name.lexeme, resolution
+        ));
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            stmt.accept(self);
+        }
+    }
+}
+
+impl ExprVisitor<()> for ResolutionPrinter<'_> {
+    fn visit_literal_expr(&mut self, _expr: &ExprLiteral) {}
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) {
+        self.expr(&expr.right);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) {
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) {
+        self.expr(&expr.expression);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) {
+        self.reference(&Expr::Variable(expr.clone()), &expr.name);
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) {
+        self.expr(&expr.value);
+        self.reference(&Expr::Assign(expr.clone()), &expr.name);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) {
+        self.expr(&expr.left);
+        self.expr(&expr.right);
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) {
+        self.expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.expr(argument);
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) {
+        self.expr(&expr.object);
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) {
+        self.expr(&expr.object);
+        self.expr(&expr.value);
+    }
+
+    fn visit_this_expr(&mut self, expr: &ExprThis) {
+        self.reference(&Expr::This(expr.clone()), &expr.keyword);
+    }
+
+    fn visit_super_expr(&mut self, expr: &ExprSuper) {
+        self.reference(&Expr::Super(expr.clone()), &expr.keyword);
+    }
+}
+
+impl StmtVisitor<()> for ResolutionPrinter<'_> {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) {
+        self.expr(&stmt.expression);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) {
+        if let Some(ref initializer) = stmt.initializer {
+            self.expr(initializer);
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) {
+        self.stmts(&stmt.statements);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) {
+        self.expr(&stmt.condition);
+        stmt.then_branch.accept(self);
+        if let Some(ref else_branch) = stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) {
+        self.expr(&stmt.condition);
+        stmt.body.accept(self);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) {
+        self.stmts(&stmt.body);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) {
+        if let Some(ref value) = stmt.value {
+            self.expr(value);
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) {
+        if let Some(ref superclass) = stmt.superclass {
+            self.expr(superclass);
+        }
+        self.stmts(&stmt.methods);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::Cell, collections::HashMap};
+use std::{cell::Cell, collections::HashMap, rc::Rc};
 
 use crate::{
     error::{self, LoxError},
@@ -153,7 +153,7 @@ impl<'a> StmtVisitor<()> for Resolver<'a> {
         self.resolve_stmt(&stmt.body);
     }
 
-    fn visit_function_stmt(&mut self, stmt: &StmtFunction) {
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) {
         self.declare(&stmt.name);
         self.define(&stmt.name);
 
@@ -314,6 +314,7 @@ impl<'a> ExprVisitor<()> for Resolver<'a> {
                 &expr.keyword,
                 "Can't use 'super' in a class with no superclass.",
             );
+            self.had_error.set(true);
         }
 
         self.resolve_local(&Expr::Super(expr.clone()), &expr.keyword);
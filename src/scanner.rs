@@ -31,13 +31,36 @@ static KEYWORDS: LazyLock<HashMap<String, TokenType>> = LazyLock::new(|| {
     m
 });
 
+/// The reserved words of Lox, in no particular order.
+pub fn keywords() -> impl Iterator<Item = &'static str> {
+    KEYWORDS.keys().map(String::as_str)
+}
+
+/// A `//` comment, which the parser never sees. Kept for tools that print
+/// source back, like the formatter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// The comment including its `//`, without the line break.
+    pub text: String,
+    pub line: usize,
+    /// Index of the token that follows the comment.
+    pub before_token: usize,
+    /// Whether code precedes the comment on its line.
+    pub trailing: bool,
+}
+
 #[derive(Debug)]
 pub struct Scanner {
     source: Vec<char>,
     tokens: Vec<Token>,
+    comments: Vec<Comment>,
     start: usize,
     current: usize,
     line: usize,
+    /// Index of the first char of the current line.
+    line_start: usize,
+    /// `line_start` as it was at `start`; strings may span lines.
+    start_line_start: usize,
 }
 
 impl Scanner {
@@ -46,18 +69,35 @@ impl Scanner {
         Self {
             source: chars,
             tokens: vec![],
+            comments: vec![],
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line_start: 0,
         }
     }
 
-    pub fn scan_tokens(mut self) -> Result<Vec<Token>, LoxError> {
+    pub fn scan_tokens(self) -> Result<Vec<Token>, LoxError> {
+        self.scan_tokens_and_comments()
+            .map(|(tokens, _comments)| tokens)
+    }
+
+    pub fn scan_tokens_and_comments(mut self) -> Result<(Vec<Token>, Vec<Comment>), LoxError> {
         let mut had_error = false;
 
+        // A `#!` line makes the file an executable script. Its newline is
+        // still scanned, so line numbers stay the same.
+        if self.source.starts_with(&['#', '!']) {
+            while self.peek() != '\n' && !self.is_at_end() {
+                self.advance();
+            }
+        }
+
         while !self.is_at_end() {
             // We are at the beginning of the next lexeme.
             self.start = self.current;
+            self.start_line_start = self.line_start;
             if self.scan_token().is_err() {
                 had_error = true;
             }
@@ -67,14 +107,11 @@ impl Scanner {
             return Err(LoxError::ScanError);
         }
 
-        self.tokens.push(Token::new(
-            TokenType::Eof,
-            "".into(),
-            Object::Null,
-            self.line,
-        ));
+        let mut eof = Token::new(TokenType::Eof, "".into(), Object::Null, self.line);
+        eof.column = self.current - self.line_start;
+        self.tokens.push(eof);
 
-        Ok(self.tokens)
+        Ok((self.tokens, self.comments))
     }
 
     fn scan_token(&mut self) -> Result<(), LoxError> {
@@ -131,12 +168,16 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.add_comment();
                 } else {
                     self.add_token(Slash, Null);
                 }
             }
             ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            '\n' => {
+                self.line += 1;
+                self.line_start = self.current;
+            }
             '"' => self.string()?,
             _ => {
                 if self.is_digit(c) {
@@ -189,10 +230,10 @@ impl Scanner {
 
     fn string(&mut self) -> Result<(), LoxError> {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
+            if self.advance() == '\n' {
                 self.line += 1;
+                self.line_start = self.current;
             }
-            self.advance();
         }
 
         if self.is_at_end() {
@@ -263,7 +304,24 @@ impl Scanner {
 
     fn add_token(&mut self, typ: TokenType, literal: Object) {
         let text = self.source[self.start..self.current].iter().collect();
-        self.tokens.push(Token::new(typ, text, literal, self.line));
+        let mut token = Token::new(typ, text, literal, self.line);
+        token.column = self.start - self.start_line_start;
+        self.tokens.push(token);
+    }
+
+    fn add_comment(&mut self) {
+        let text: String = self.source[self.start..self.current].iter().collect();
+        let trailing = self
+            .tokens
+            .last()
+            .is_some_and(|token| token.line == self.line);
+
+        self.comments.push(Comment {
+            text: text.trim_end().to_string(),
+            line: self.line,
+            before_token: self.tokens.len(),
+            trailing,
+        });
     }
 }
 
@@ -271,6 +329,10 @@ impl Scanner {
 mod test {
     use super::*;
 
+    fn at(column: usize, token: Token) -> Token {
+        Token { column, ..token }
+    }
+
     #[test]
     fn scan_tokens_succeed_for_correct_if_expression() {
         use TokenType::*;
@@ -280,20 +342,26 @@ mod test {
         let tokens = scanner.scan_tokens().unwrap();
 
         let answers = vec![
-            Token::new(If, "if".into(), Object::Null, 1),
-            Token::new(True, "true".into(), Object::Null, 1),
-            Token::new(LeftBrace, "{".into(), Object::Null, 1),
-            Token::new(Identifier, "id_a".into(), Object::Null, 1),
-            Token::new(Plus, "+".into(), Object::Null, 1),
-            Token::new(Number, "123.456".into(), Object::Num(123.456), 1),
-            Token::new(RightBrace, "}".into(), Object::Null, 1),
-            Token::new(Else, "else".into(), Object::Null, 1),
-            Token::new(LeftBrace, "{".into(), Object::Null, 1),
-            Token::new(String, "\"hello\"".into(), Object::Str("hello".into()), 1),
-            Token::new(BangEqual, "!=".into(), Object::Null, 1),
-            Token::new(Number, "789".into(), Object::Num(789f64), 1),
-            Token::new(RightBrace, "}".into(), Object::Null, 1),
-            Token::new(Eof, "".into(), Object::Null, 1),
+            at(0, Token::new(If, "if".into(), Object::Null, 1)),
+            at(3, Token::new(True, "true".into(), Object::Null, 1)),
+            at(8, Token::new(LeftBrace, "{".into(), Object::Null, 1)),
+            at(10, Token::new(Identifier, "id_a".into(), Object::Null, 1)),
+            at(15, Token::new(Plus, "+".into(), Object::Null, 1)),
+            at(
+                17,
+                Token::new(Number, "123.456".into(), Object::Num(123.456), 1),
+            ),
+            at(25, Token::new(RightBrace, "}".into(), Object::Null, 1)),
+            at(27, Token::new(Else, "else".into(), Object::Null, 1)),
+            at(32, Token::new(LeftBrace, "{".into(), Object::Null, 1)),
+            at(
+                34,
+                Token::new(String, "\"hello\"".into(), Object::Str("hello".into()), 1),
+            ),
+            at(42, Token::new(BangEqual, "!=".into(), Object::Null, 1)),
+            at(45, Token::new(Number, "789".into(), Object::Num(789f64), 1)),
+            at(49, Token::new(RightBrace, "}".into(), Object::Null, 1)),
+            at(50, Token::new(Eof, "".into(), Object::Null, 1)),
         ];
 
         assert_eq!(tokens, answers);
@@ -311,10 +379,10 @@ mod test {
         let tokens = scanner.scan_tokens().unwrap();
 
         let answers = vec![
-            Token::new(Number, "123".into(), Object::Num(123f64), 2),
-            Token::new(Plus, "+".into(), Object::Null, 3),
-            Token::new(Number, "456".into(), Object::Num(456f64), 4),
-            Token::new(Eof, "".into(), Object::Null, 4),
+            at(0, Token::new(Number, "123".into(), Object::Num(123f64), 2)),
+            at(0, Token::new(Plus, "+".into(), Object::Null, 3)),
+            at(0, Token::new(Number, "456".into(), Object::Num(456f64), 4)),
+            at(3, Token::new(Eof, "".into(), Object::Null, 4)),
         ];
 
         assert_eq!(tokens, answers);
@@ -330,4 +398,19 @@ mod test {
         let scanner = Scanner::new(source);
         let _tokens = scanner.scan_tokens().unwrap(); // should panic
     }
+
+    #[test]
+    fn scan_tokens_skips_shebang_line() {
+        use TokenType::*;
+
+        let source = "#!/usr/bin/env jlox\nprint 1;";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+
+        let typs: Vec<_> = tokens.iter().map(|token| token.typ).collect();
+        assert_eq!(typs, vec![Print, Number, Semicolon, Eof]);
+        assert_eq!(tokens[0].line, 2);
+
+        // Only on the first line.
+        assert!(Scanner::new("print 1;\n#!").scan_tokens().is_err());
+    }
 }
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use crate::{expr::Expr, token::Token};
+
+/// Local scopes for passes that walk a resolved program. Callers open and
+/// close them exactly where the `Resolver` opens and closes its own, so the
+/// depth it recorded for a variable leads back to its declaration. Each name
+/// maps to whatever the pass knows about it.
+#[derive(Debug)]
+pub(crate) struct Scopes<'a, T> {
+    locals: &'a HashMap<Expr, usize>,
+    scopes: Vec<HashMap<String, T>>,
+}
+
+impl<'a, T> Scopes<'a, T> {
+    pub(crate) fn new(locals: &'a HashMap<Expr, usize>) -> Self {
+        Self {
+            locals,
+            scopes: vec![],
+        }
+    }
+
+    pub(crate) fn begin(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Closes the innermost scope and returns what was declared in it.
+    pub(crate) fn end(&mut self) -> HashMap<String, T> {
+        self.scopes.pop().expect("unbalanced scopes")
+    }
+
+    /// Whether no local scope is open, so declarations are globals.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    /// Declares `name` in the innermost scope. Returns false, declaring
+    /// nothing, for globals.
+    pub(crate) fn declare(&mut self, name: &str, value: T) -> bool {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(name.to_string(), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the resolver found `expr` in a local scope rather than leaving
+    /// it to the globals.
+    pub(crate) fn is_local(&self, expr: &Expr) -> bool {
+        self.locals.contains_key(expr)
+    }
+
+    /// What was declared for the local variable `expr` refers to. `None` for
+    /// globals, and for names this pass did not declare.
+    pub(crate) fn get(&self, expr: &Expr, name: &Token) -> Option<&T> {
+        let index = self.index(expr)?;
+        self.scopes[index].get(&name.lexeme)
+    }
+
+    pub(crate) fn get_mut(&mut self, expr: &Expr, name: &Token) -> Option<&mut T> {
+        let index = self.index(expr)?;
+        self.scopes[index].get_mut(&name.lexeme)
+    }
+
+    fn index(&self, expr: &Expr) -> Option<usize> {
+        let depth = self.locals.get(expr)?;
+        self.scopes.len().checked_sub(depth + 1)
+    }
+}
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::{expr::Expr, token::Token};
 
 pub trait StmtVisitor<R> {
@@ -7,7 +9,7 @@ pub trait StmtVisitor<R> {
     fn visit_block_stmt(&mut self, stmt: &StmtBlock) -> R;
     fn visit_if_stmt(&mut self, stmt: &StmtIf) -> R;
     fn visit_while_stmt(&mut self, stmt: &StmtWhile) -> R;
-    fn visit_function_stmt(&mut self, stmt: &StmtFunction) -> R;
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) -> R;
     fn visit_return_stmt(&mut self, stmt: &StmtReturn) -> R;
     fn visit_class_stmt(&mut self, stmt: &StmtClass) -> R;
 }
@@ -20,7 +22,9 @@ pub enum Stmt {
     Block(StmtBlock),
     If(StmtIf),
     While(StmtWhile),
-    Function(StmtFunction),
+    // Shared with every `LoxFunction` created from it, so declaring or binding
+    // a function never copies its body.
+    Function(Rc<StmtFunction>),
     Return(StmtReturn),
     Class(StmtClass),
 }
@@ -43,12 +47,31 @@ impl Stmt {
         }
     }
 
+    /// The line the statement starts on. Only an expression statement made
+    /// of literals, or a block of them, has none.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Stmt::Expression(stmt) => stmt.expression.line(),
+            Stmt::Print(stmt) => Some(stmt.keyword.line),
+            Stmt::Var(stmt) => Some(stmt.name.line),
+            Stmt::Block(stmt) => stmt.statements.iter().find_map(Stmt::line),
+            Stmt::If(stmt) => Some(stmt.keyword.line),
+            Stmt::While(stmt) => Some(stmt.keyword.line),
+            Stmt::Function(stmt) => Some(stmt.name.line),
+            Stmt::Return(stmt) => Some(stmt.keyword.line),
+            Stmt::Class(stmt) => Some(stmt.name.line),
+        }
+    }
+
     pub fn new_expression(expression: Expr) -> Self {
         Self::Expression(StmtExpression { expression })
     }
 
-    pub fn new_print(expression: Expr) -> Self {
-        Self::Print(StmtPrint { expression })
+    pub fn new_print(keyword: Token, expression: Expr) -> Self {
+        Self::Print(StmtPrint {
+            keyword,
+            expression,
+        })
     }
 
     pub fn new_var(name: Token, initializer: Option<Expr>) -> Self {
@@ -59,20 +82,30 @@ impl Stmt {
         Self::Block(StmtBlock { statements })
     }
 
-    pub fn new_if(condition: Expr, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>>) -> Self {
+    pub fn new_if(
+        keyword: Token,
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    ) -> Self {
         Self::If(StmtIf {
+            keyword,
             condition,
             then_branch,
             else_branch,
         })
     }
 
-    pub fn new_while(condition: Expr, body: Box<Stmt>) -> Self {
-        Self::While(StmtWhile { condition, body })
+    pub fn new_while(keyword: Token, condition: Expr, body: Box<Stmt>) -> Self {
+        Self::While(StmtWhile {
+            keyword,
+            condition,
+            body,
+        })
     }
 
     pub fn new_function(name: Box<Token>, params: Vec<Token>, body: Vec<Stmt>) -> Self {
-        Self::Function(StmtFunction { name, params, body })
+        Self::Function(Rc::new(StmtFunction { name, params, body }))
     }
 
     pub fn new_return(keyword: Token, value: Option<Expr>) -> Self {
@@ -95,6 +128,7 @@ pub struct StmtExpression {
 
 #[derive(Debug, Clone)]
 pub struct StmtPrint {
+    pub keyword: Token,
     pub expression: Expr,
 }
 
@@ -111,6 +145,7 @@ pub struct StmtBlock {
 
 #[derive(Debug, Clone)]
 pub struct StmtIf {
+    pub keyword: Token,
     pub condition: Expr,
     pub then_branch: Box<Stmt>,
     pub else_branch: Option<Box<Stmt>>,
@@ -118,6 +153,8 @@ pub struct StmtIf {
 
 #[derive(Debug, Clone)]
 pub struct StmtWhile {
+    /// `while`, or `for` for a desugared `for` loop.
+    pub keyword: Token,
     pub condition: Expr,
     pub body: Box<Stmt>,
 }
@@ -0,0 +1,142 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::LoxError, interpreter::Interpreter, lox_callable::LoxCallable, object::Object,
+    resolver::Resolver, stmt::Stmt, token::Token, Timings,
+};
+
+/// Test files end in this; tests in them are functions named `test_*`.
+const SUFFIX: &str = "_test.lox";
+const PREFIX: &str = "test_";
+
+/// How one test function went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub name: String,
+    /// Why the test failed, or `None` if it passed.
+    pub failure: Option<String>,
+}
+
+/// Every `*_test.lox` file under `path`, sorted, or `path` itself if it is
+/// a file.
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = vec![];
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(discover(&path)?);
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(SUFFIX))
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Runs every top-level `test_*` function in `source`, in order. Each runs
+/// in a fresh `Interpreter` after the script's top-level statements, so one
+/// test cannot break another. Fails only if the script has static errors,
+/// which are reported as usual.
+pub fn run_tests(source: &str) -> Result<Vec<Outcome>, LoxError> {
+    let statements = crate::parse(source, false, &mut Timings::default())?;
+    Resolver::new(&mut Interpreter::new()).resolve(&statements)?;
+
+    let tests = statements.iter().filter_map(|stmt| match stmt {
+        Stmt::Function(function) if function.name.lexeme.starts_with(PREFIX) => {
+            Some((*function.name).clone())
+        }
+        _ => None,
+    });
+
+    Ok(tests
+        .map(|name| Outcome {
+            failure: run_test(&statements, &name).err(),
+            name: name.lexeme,
+        })
+        .collect())
+}
+
+fn run_test(statements: &[Stmt], name: &Token) -> Result<(), String> {
+    let mut interpreter = Interpreter::new();
+    crate::execute(
+        statements.to_vec(),
+        &mut interpreter,
+        &mut Timings::default(),
+    )
+    .map_err(|err| failure(&err))?;
+
+    let test = interpreter
+        .globals
+        .borrow()
+        .get(name)
+        .map_err(|err| failure(&err))?;
+    let Object::Callable(test) = test else {
+        return Err(format!("'{}' is not a function.", name.lexeme));
+    };
+    if test.arity() != 0 {
+        return Err("Test functions take no arguments.".to_string());
+    }
+
+    test.call(&mut interpreter, &[])
+        .map(|_| ())
+        .map_err(|err| failure(&err))
+}
+
+fn failure(err: &LoxError) -> String {
+    match err {
+        LoxError::RuntimeError(token, message) => format!("{} [line {}]", message, token.line),
+        LoxError::AssertionFailed(line, message) => {
+            format!("Assertion failed: {} [line {}]", message, line)
+        }
+        LoxError::CallDepthExceeded(line) => format!("Stack overflow. [line {}]", line),
+        LoxError::Exit(code) => format!("Called exit({}).", code),
+        _ => "Failed.".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn isolates_failing_tests() {
+        let source = "var calls = 0;\n\
+                      fun test_counts() { calls = calls + 1; assert(calls == 1, \"shared\"); }\n\
+                      fun test_fails() {\n  assert(1 == 2, \"math\");\n}\n\
+                      fun test_errors() { nil(); }\n\
+                      fun helper() { assert(false, \"not a test\"); }\n\
+                      fun test_again() { test_counts(); }\n";
+
+        let outcomes: Vec<_> = run_tests(source)
+            .unwrap()
+            .into_iter()
+            .map(|outcome| (outcome.name, outcome.failure))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("test_counts".to_string(), None),
+                (
+                    "test_fails".to_string(),
+                    Some("Assertion failed: math [line 4]".to_string())
+                ),
+                (
+                    "test_errors".to_string(),
+                    Some("Can only call functions and classes. [line 6]".to_string())
+                ),
+                ("test_again".to_string(), None),
+            ]
+        );
+    }
+}
@@ -6,6 +6,9 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Object,
     pub line: usize,
+    /// Chars between the start of the line and the token's first char. Zero
+    /// for tokens that don't come from source.
+    pub column: usize,
 }
 
 impl Token {
@@ -15,6 +18,7 @@ impl Token {
             lexeme,
             literal,
             line,
+            column: 0,
         }
     }
 }
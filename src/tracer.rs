@@ -0,0 +1,100 @@
+use crate::{error::LoxError, object::Object, stmt::Stmt, token::Token};
+
+/// Logs what the program does to stderr for `--trace`: each statement with
+/// its line, each variable defined or assigned, and each call and return,
+/// indented by call depth.
+#[derive(Debug)]
+pub struct Tracer {
+    lines: Vec<String>,
+    depth: usize,
+    /// Only calls to these functions, and everything they do, are traced.
+    /// Everything is traced when this is empty.
+    functions: Vec<String>,
+    /// The depth of the filtered call being traced, if any.
+    traced_call: Option<usize>,
+}
+
+impl Tracer {
+    pub fn new(source: &str) -> Self {
+        Self {
+            lines: source.lines().map(|line| line.trim().to_string()).collect(),
+            depth: 0,
+            functions: vec![],
+            traced_call: None,
+        }
+    }
+
+    /// Traces only calls to `function`, and what happens inside them. Can be
+    /// given more than once.
+    pub fn only(&mut self, function: &str) {
+        self.functions.push(function.to_string());
+    }
+
+    fn tracing(&self) -> bool {
+        self.functions.is_empty() || self.traced_call.is_some()
+    }
+
+    fn log(&self, event: std::fmt::Arguments<'_>) {
+        if self.tracing() {
+            // Filtered traces are indented from the call that started them.
+            let depth = self.depth - self.traced_call.unwrap_or(0);
+            eprintln!("{:indent$}{}", "", event, indent = depth * 2);
+        }
+    }
+
+    pub(crate) fn statement(&self, stmt: &Stmt) {
+        // Blocks only group statements; the ones inside are traced instead.
+        if matches!(stmt, Stmt::Block(_)) {
+            return;
+        }
+        if let Some(line) = stmt.line() {
+            let source = self.lines.get(line - 1).map_or("", String::as_str);
+            self.log(format_args!("[line {}] {}", line, source));
+        }
+    }
+
+    pub(crate) fn define(&self, name: &Token, value: &Object) {
+        self.log(format_args!("define {} = {}", name.lexeme, Value(value)));
+    }
+
+    pub(crate) fn assign(&self, name: &Token, value: &Object) {
+        self.log(format_args!("assign {} = {}", name.lexeme, Value(value)));
+    }
+
+    pub(crate) fn call(&mut self, function: &str, arguments: &[Object]) {
+        if self.traced_call.is_none() && self.functions.iter().any(|name| name == function) {
+            self.traced_call = Some(self.depth);
+        }
+
+        let arguments: Vec<String> = arguments
+            .iter()
+            .map(|argument| Value(argument).to_string())
+            .collect();
+        self.log(format_args!("call {}({})", function, arguments.join(", ")));
+        self.depth += 1;
+    }
+
+    pub(crate) fn ret(&mut self, function: &str, result: &Result<Object, LoxError>) {
+        self.depth -= 1;
+        match result {
+            Ok(value) => self.log(format_args!("{} returned {}", function, Value(value))),
+            Err(_) => self.log(format_args!("{} failed", function)),
+        }
+
+        if self.traced_call == Some(self.depth) {
+            self.traced_call = None;
+        }
+    }
+}
+
+/// Shows strings quoted, so they stand apart from other values.
+struct Value<'a>(&'a Object);
+
+impl std::fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Object::Str(string) => write!(f, "\"{}\"", string),
+            value => write!(f, "{}", value),
+        }
+    }
+}
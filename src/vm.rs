@@ -0,0 +1,699 @@
+//! A bytecode backend: `Compiler` turns the resolved AST into `Op`s and `Vm`
+//! runs them on a value stack, with the same semantics and error messages as
+//! the tree-walking `Interpreter`.
+
+mod chunk;
+mod compiler;
+mod value;
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::SystemTime};
+
+use crate::{
+    error::{self, LoxError},
+    object::Object,
+    token::Token,
+    token_type::TokenType,
+};
+
+pub use compiler::Compiler;
+pub use value::Value;
+
+use chunk::Op;
+use value::*;
+
+const FRAMES_MAX: usize = 4096;
+
+#[derive(Debug)]
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // Index of the frame's slot zero on the value stack.
+    slots: usize,
+}
+
+#[derive(Debug)]
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    init_string: Rc<str>,
+    args: Vec<String>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut vm = Self {
+            stack: Vec::with_capacity(256),
+            frames: Vec::with_capacity(64),
+            globals: HashMap::new(),
+            open_upvalues: vec![],
+            init_string: "init".into(),
+            args: vec![],
+        };
+
+        vm.define_native("clock", 0, |_vm, _arguments| {
+            // the number of non-leap seconds since the start of 1970 UTC.
+            let time = SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64();
+            Ok(Value::Num(time))
+        });
+
+        // These behave exactly like the tree-walker's natives.
+        vm.define_native("argc", 0, |vm, _arguments| {
+            Ok(Value::Num(vm.args.len() as f64))
+        });
+
+        vm.define_native("argv", 1, |vm, arguments| {
+            let arg = match arguments[0] {
+                Value::Num(index) if index >= 0.0 && index.fract() == 0.0 => {
+                    vm.args.get(index as usize)
+                }
+                _ => None,
+            };
+            Ok(arg.map_or(Value::Nil, |arg| Value::Str(arg.as_str().into())))
+        });
+
+        vm.define_native("exit", 1, |_vm, arguments| {
+            let code = match arguments[0] {
+                Value::Nil => 0,
+                Value::Num(code) => code as i32,
+                ref value => {
+                    eprintln!("{}", value);
+                    1
+                }
+            };
+            Err(LoxError::Exit(code))
+        });
+
+        vm.define_native("assert", 2, |vm, arguments| {
+            if arguments[0].is_truthy() {
+                return Ok(Value::Nil);
+            }
+            Err(LoxError::AssertionFailed(
+                vm.line(),
+                arguments[1].to_string(),
+            ))
+        });
+
+        vm
+    }
+
+    fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = Native { arity, function };
+        self.globals
+            .insert(name.into(), Value::Native(Rc::new(native)));
+    }
+
+    /// Sets what the `argc` and `argv` natives see.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Names currently defined as globals, natives included.
+    pub fn global_names(&self) -> Vec<String> {
+        self.globals.keys().map(|name| name.to_string()).collect()
+    }
+
+    /// Each global with its value as `print` would show it, sorted by name.
+    pub fn global_values(&self) -> Vec<(String, String)> {
+        let mut values: Vec<_> = self
+            .globals
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        values.sort();
+        values
+    }
+
+    pub fn interpret(&mut self, function: Rc<Function>) -> Result<(), LoxError> {
+        self.evaluate(function).map(|_| ())
+    }
+
+    /// Runs `function` like `interpret` and returns the value the script
+    /// returns; see `Compiler::compile_expression`.
+    pub fn evaluate(&mut self, function: Rc<Function>) -> Result<Value, LoxError> {
+        let closure = Rc::new(Closure {
+            function,
+            upvalues: vec![],
+        });
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: 0,
+        });
+
+        let result = self.run();
+
+        if let Err(ref err) = result {
+            match err {
+                LoxError::RuntimeError(token, message) => error::lox_runtime_error(token, message),
+                LoxError::AssertionFailed(line, message) => {
+                    error::lox_assertion_failed(*line, message)
+                }
+                _ => (),
+            }
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<Value, LoxError> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let op = frame.closure.function.chunk.code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                Op::Constant(index) => {
+                    let constant = self.constant(index);
+                    self.stack.push(constant);
+                }
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.stack.pop();
+                }
+                Op::GetLocal(slot) => {
+                    let slot = self.frame().slots + slot as usize;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                Op::SetLocal(slot) => {
+                    let slot = self.frame().slots + slot as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                Op::GetGlobal(index) => {
+                    let name = self.string_constant(index);
+                    match self.globals.get(&name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => {
+                            return Err(
+                                self.runtime_error(format!("Undefined variable '{}'.", name))
+                            )
+                        }
+                    }
+                }
+                Op::DefineGlobal(index) => {
+                    let name = self.string_constant(index);
+                    let value = self.stack.pop().unwrap();
+                    self.globals.insert(name, value);
+                }
+                Op::SetGlobal(index) => {
+                    let name = self.string_constant(index);
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(global) => *global = value,
+                        None => {
+                            return Err(
+                                self.runtime_error(format!("Undefined variable '{}'.", name))
+                            )
+                        }
+                    }
+                }
+                Op::GetUpvalue(index) => {
+                    let upvalue = self.frame().closure.upvalues[index as usize].clone();
+                    let value = match *upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[slot].clone(),
+                        Upvalue::Closed(ref value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                Op::SetUpvalue(index) => {
+                    let upvalue = self.frame().closure.upvalues[index as usize].clone();
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match *upvalue {
+                        Upvalue::Open(slot) => self.stack[slot] = value,
+                        Upvalue::Closed(ref mut closed) => *closed = value,
+                    }
+                }
+                Op::GetProperty(index) => {
+                    let instance = match self.peek(0) {
+                        Value::Instance(instance) => instance.clone(),
+                        _ => {
+                            return Err(
+                                self.runtime_error("Only instances have properties.".to_string())
+                            )
+                        }
+                    };
+                    let name = self.string_constant(index);
+
+                    let field = instance.fields.borrow().get(&name).cloned();
+                    match field {
+                        Some(value) => {
+                            self.stack.pop();
+                            self.stack.push(value);
+                        }
+                        None => self.bind_method(&instance.class, &name)?,
+                    }
+                }
+                Op::SetProperty(index) => {
+                    let instance = match self.peek(1) {
+                        Value::Instance(instance) => instance.clone(),
+                        _ => {
+                            return Err(
+                                self.runtime_error("Only instances have fields.".to_string())
+                            )
+                        }
+                    };
+                    let name = self.string_constant(index);
+
+                    let value = self.stack.pop().unwrap();
+                    instance.fields.borrow_mut().insert(name, value.clone());
+                    self.stack.pop();
+                    self.stack.push(value);
+                }
+                Op::GetSuper(index) => {
+                    let name = self.string_constant(index);
+                    let superclass = match self.stack.pop() {
+                        Some(Value::Class(class)) => class,
+                        _ => unreachable!("'super' must be a class."),
+                    };
+                    self.bind_method(&superclass, &name)?;
+                }
+                Op::Equal => {
+                    let b = self.stack.pop().unwrap();
+                    let a = self.stack.pop().unwrap();
+                    self.stack.push(Value::Bool(a == b));
+                }
+                Op::NotEqual => {
+                    let b = self.stack.pop().unwrap();
+                    let a = self.stack.pop().unwrap();
+                    self.stack.push(Value::Bool(a != b));
+                }
+                Op::Greater => self.number_operands(|a, b| Value::Bool(a > b))?,
+                Op::GreaterEqual => self.number_operands(|a, b| Value::Bool(a >= b))?,
+                Op::Less => self.number_operands(|a, b| Value::Bool(a < b))?,
+                Op::LessEqual => self.number_operands(|a, b| Value::Bool(a <= b))?,
+                Op::Add => {
+                    let b = self.stack.pop().unwrap();
+                    let a = self.stack.pop().unwrap();
+                    match (a, b) {
+                        (Value::Num(a), Value::Num(b)) => self.stack.push(Value::Num(a + b)),
+                        (Value::Str(a), Value::Str(b)) => {
+                            let mut string = String::with_capacity(a.len() + b.len());
+                            string.push_str(&a);
+                            string.push_str(&b);
+                            self.stack.push(Value::Str(string.into()));
+                        }
+                        _ => {
+                            return Err(self.runtime_error(
+                                "Operands must be two numbers or two strings.".to_string(),
+                            ))
+                        }
+                    }
+                }
+                Op::Subtract => self.number_operands(|a, b| Value::Num(a - b))?,
+                Op::Multiply => self.number_operands(|a, b| Value::Num(a * b))?,
+                Op::Divide => self.number_operands(|a, b| Value::Num(a / b))?,
+                Op::Not => {
+                    let value = self.stack.pop().unwrap();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                Op::Negate => match self.peek(0) {
+                    Value::Num(value) => {
+                        let value = -*value;
+                        self.stack.pop();
+                        self.stack.push(Value::Num(value));
+                    }
+                    _ => return Err(self.runtime_error("Operand must be a number.".to_string())),
+                },
+                Op::Print => {
+                    let value = self.stack.pop().unwrap();
+                    println!("{}", value);
+                }
+                Op::Jump(offset) => self.frame_mut().ip += offset as usize,
+                Op::JumpIfFalse(offset) => {
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset as usize;
+                    }
+                }
+                Op::Loop(offset) => self.frame_mut().ip -= offset as usize,
+                Op::Call(argc) => {
+                    let callee = self.peek(argc as usize).clone();
+                    self.call_value(callee, argc as usize)?;
+                }
+                Op::Invoke(index, argc) => {
+                    let name = self.string_constant(index);
+                    self.invoke(&name, argc as usize)?;
+                }
+                Op::SuperInvoke(index, argc) => {
+                    let name = self.string_constant(index);
+                    let superclass = match self.stack.pop() {
+                        Some(Value::Class(class)) => class,
+                        _ => unreachable!("'super' must be a class."),
+                    };
+                    self.invoke_from_class(&superclass, &name, argc as usize)?;
+                }
+                Op::Closure(index) => {
+                    let function = match self.constant(index) {
+                        Value::Function(function) => function,
+                        _ => unreachable!("Closure operand must be a function."),
+                    };
+
+                    let slots = self.frame().slots;
+                    let upvalues = function
+                        .upvalues
+                        .iter()
+                        .map(|upvalue| {
+                            if upvalue.is_local {
+                                self.capture_upvalue(slots + upvalue.index as usize)
+                            } else {
+                                self.frame().closure.upvalues[upvalue.index as usize].clone()
+                            }
+                        })
+                        .collect();
+
+                    let closure = Closure { function, upvalues };
+                    self.stack.push(Value::Closure(Rc::new(closure)));
+                }
+                Op::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.stack.pop();
+                }
+                Op::Return => {
+                    let result = self.stack.pop().unwrap();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+
+                    if self.frames.is_empty() {
+                        self.stack.clear();
+                        return Ok(result);
+                    }
+
+                    self.stack.truncate(frame.slots);
+                    self.stack.push(result);
+                }
+                Op::Class(index) => {
+                    let class = Class {
+                        name: self.string_constant(index),
+                        methods: RefCell::new(HashMap::new()),
+                    };
+                    self.stack.push(Value::Class(Rc::new(class)));
+                }
+                Op::Inherit => {
+                    let superclass = match self.peek(1) {
+                        Value::Class(class) => class.clone(),
+                        _ => {
+                            return Err(
+                                self.runtime_error("Superclass must be a class.".to_string())
+                            )
+                        }
+                    };
+                    if let Value::Class(subclass) = self.peek(0) {
+                        subclass
+                            .methods
+                            .borrow_mut()
+                            .extend(superclass.methods.borrow().clone());
+                    }
+                    self.stack.pop();
+                }
+                Op::Method(index) => {
+                    let name = self.string_constant(index);
+                    let method = match self.stack.pop() {
+                        Some(Value::Closure(closure)) => closure,
+                        _ => unreachable!("Method must be a closure."),
+                    };
+                    if let Value::Class(class) = self.peek(0) {
+                        class.methods.borrow_mut().insert(name, method);
+                    }
+                }
+            }
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn constant(&self, index: u16) -> Value {
+        self.frame().closure.function.chunk.constants[index as usize].clone()
+    }
+
+    fn string_constant(&self, index: u16) -> Rc<str> {
+        match self.constant(index) {
+            Value::Str(string) => string,
+            _ => unreachable!("Name operand must be a string constant."),
+        }
+    }
+
+    fn number_operands(&mut self, op: fn(f64, f64) -> Value) -> Result<(), LoxError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Num(a), Value::Num(b)) => {
+                let result = op(*a, *b);
+                self.stack.pop();
+                self.stack.pop();
+                self.stack.push(result);
+                Ok(())
+            }
+            _ => Err(self.runtime_error("Operands must be numbers.".to_string())),
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, argc: usize) -> Result<(), LoxError> {
+        match callee {
+            Value::Closure(closure) => self.call(closure, argc),
+            Value::Native(native) => {
+                self.check_arity(native.arity, argc)?;
+
+                let arguments = self.stack.split_off(self.stack.len() - argc);
+                let result = (native.function)(self, &arguments)?;
+
+                self.stack.pop();
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::Class(class) => {
+                let slot = self.stack.len() - argc - 1;
+                let instance = Instance {
+                    class: class.clone(),
+                    fields: RefCell::new(HashMap::new()),
+                };
+                self.stack[slot] = Value::Instance(Rc::new(instance));
+
+                let initializer = class.methods.borrow().get(&self.init_string).cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, argc),
+                    None => self.check_arity(0, argc),
+                }
+            }
+            Value::BoundMethod(bound) => {
+                let slot = self.stack.len() - argc - 1;
+                self.stack[slot] = bound.receiver.clone();
+                self.call(bound.method.clone(), argc)
+            }
+            _ => Err(self.runtime_error("Can only call functions and classes.".to_string())),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, argc: usize) -> Result<(), LoxError> {
+        self.check_arity(closure.function.arity, argc)?;
+
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.runtime_error("Stack overflow.".to_string()));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - argc - 1,
+        });
+        Ok(())
+    }
+
+    fn check_arity(&self, arity: usize, argc: usize) -> Result<(), LoxError> {
+        if arity == argc {
+            return Ok(());
+        }
+
+        Err(self.runtime_error(format!("Expected {} arguments but got {}.", arity, argc)))
+    }
+
+    fn invoke(&mut self, name: &Rc<str>, argc: usize) -> Result<(), LoxError> {
+        let instance = match self.peek(argc) {
+            Value::Instance(instance) => instance.clone(),
+            _ => return Err(self.runtime_error("Only instances have properties.".to_string())),
+        };
+
+        let field = instance.fields.borrow().get(name).cloned();
+        if let Some(field) = field {
+            let slot = self.stack.len() - argc - 1;
+            self.stack[slot] = field.clone();
+            return self.call_value(field, argc);
+        }
+
+        self.invoke_from_class(&instance.class, name, argc)
+    }
+
+    fn invoke_from_class(
+        &mut self,
+        class: &Rc<Class>,
+        name: &Rc<str>,
+        argc: usize,
+    ) -> Result<(), LoxError> {
+        let method = class.methods.borrow().get(name).cloned();
+        match method {
+            Some(method) => self.call(method, argc),
+            None => Err(self.runtime_error(format!("Undefined property '{}'.", name))),
+        }
+    }
+
+    // Replaces the instance on top of the stack with its method `name`.
+    fn bind_method(&mut self, class: &Rc<Class>, name: &Rc<str>) -> Result<(), LoxError> {
+        let method = class.methods.borrow().get(name).cloned();
+        match method {
+            Some(method) => {
+                let receiver = self.stack.pop().unwrap();
+                let bound = BoundMethod { receiver, method };
+                self.stack.push(Value::BoundMethod(Rc::new(bound)));
+                Ok(())
+            }
+            None => Err(self.runtime_error(format!("Undefined property '{}'.", name))),
+        }
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(s) if s == slot));
+
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    // Moves every captured variable at or above `last` off the stack.
+    fn close_upvalues(&mut self, last: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let slot = match *upvalue.borrow() {
+                Upvalue::Open(slot) => slot,
+                Upvalue::Closed(_) => return false,
+            };
+            if slot < last {
+                return true;
+            }
+            *upvalue.borrow_mut() = Upvalue::Closed(stack[slot].clone());
+            false
+        });
+    }
+
+    /// The line of the instruction being run.
+    fn line(&self) -> usize {
+        let frame = self.frame();
+        frame.closure.function.chunk.lines[frame.ip - 1]
+    }
+
+    fn runtime_error(&self, message: String) -> LoxError {
+        let token = Token::new(TokenType::Eof, String::new(), Object::Null, self.line());
+
+        LoxError::RuntimeError(token, message)
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{interpreter::Interpreter, parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    use super::*;
+
+    // Runs `source` and returns the value left in the global `result`.
+    fn run(source: &str) -> Result<Value, LoxError> {
+        let tokens = Scanner::new(source).scan_tokens()?;
+        let statements = Parser::new(tokens).parse()?;
+        Resolver::new(&mut Interpreter::new()).resolve(&statements)?;
+        let function = Compiler::new().compile(&statements)?;
+
+        let mut vm = Vm::new();
+        vm.interpret(function)?;
+        Ok(vm.globals.get("result").cloned().unwrap_or(Value::Nil))
+    }
+
+    #[test]
+    fn vm_evaluates_expressions() {
+        assert_eq!(
+            run("var result = (1 + 2) * 3 - 4 / 2;"),
+            Ok(Value::Num(7f64))
+        );
+        assert_eq!(
+            run("var result = !(1 >= 2) and nil or \"x\";"),
+            Ok(Value::Str("x".into()))
+        );
+        assert_eq!(
+            run("var result = \"a\" + \"b\" == \"ab\";"),
+            Ok(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn vm_closes_over_loop_variables() {
+        // Each iteration's `j` is a new variable, closed over when the body's
+        // scope ends.
+        let source = "
+            var first;
+            var second;
+            var third;
+            for (var i = 1; i <= 3; i = i + 1) {
+                var j = i;
+                fun get() { return j; }
+                if (i == 1) first = get;
+                if (i == 2) second = get;
+                if (i == 3) third = get;
+            }
+            var result = first() * 100 + second() * 10 + third();";
+
+        assert_eq!(run(source), Ok(Value::Num(123f64)));
+    }
+
+    #[test]
+    fn vm_supports_inheritance_and_super() {
+        let source = "
+            class A {
+                init(n) { this.n = n; }
+                get() { return this.n; }
+            }
+            class B < A {
+                init(n) { super.init(n * 2); }
+                get() { var f = super.get; return f() + 1; }
+            }
+            var result = B(20).get();";
+
+        assert_eq!(run(source), Ok(Value::Num(41f64)));
+    }
+
+    #[test]
+    fn vm_reports_runtime_errors_with_line() {
+        match run("fun f() {\n  return 1 + \"a\";\n}\nvar result = f();") {
+            Err(LoxError::RuntimeError(token, message)) => {
+                assert_eq!(token.line, 2);
+                assert_eq!(message, "Operands must be two numbers or two strings.");
+            }
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+}
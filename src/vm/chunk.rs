@@ -0,0 +1,75 @@
+use super::value::Value;
+
+/// A single VM instruction.
+///
+/// Operands are stored inline, so every instruction fits in four bytes.
+/// `u16` operands index the chunk's constant pool or are jump offsets, `u8`
+/// operands are stack slots, upvalue indices or argument counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Constant(u16),
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal(u8),
+    SetLocal(u8),
+    GetGlobal(u16),
+    DefineGlobal(u16),
+    SetGlobal(u16),
+    GetUpvalue(u8),
+    SetUpvalue(u8),
+    GetProperty(u16),
+    SetProperty(u16),
+    GetSuper(u16),
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump(u16),
+    JumpIfFalse(u16),
+    Loop(u16),
+    Call(u8),
+    Invoke(u16, u8),
+    SuperInvoke(u16, u8),
+    Closure(u16),
+    CloseUpvalue,
+    Return,
+    Class(u16),
+    Inherit,
+    Method(u16),
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub lines: Vec<usize>,
+    pub constants: Vec<Value>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: Op, line: usize) -> usize {
+        self.code.push(op);
+        self.lines.push(line);
+        self.code.len() - 1
+    }
+
+    /// Returns the index of `value` in the constant pool, adding it if needed.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        if let Some(index) = self.constants.iter().position(|c| c.same_constant(&value)) {
+            return index;
+        }
+
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+}
@@ -0,0 +1,665 @@
+use std::{cell::Cell, rc::Rc};
+
+use crate::{
+    error::{self, LoxError},
+    expr::*,
+    object::Object,
+    stmt::*,
+    token::Token,
+    token_type::TokenType,
+};
+
+use super::{
+    chunk::{Chunk, Op},
+    value::{Function, UpvalueDesc, Value},
+};
+
+const MAX_LOCALS: usize = u8::MAX as usize + 1;
+const MAX_UPVALUES: usize = u8::MAX as usize + 1;
+
+/// Compiles a resolved program into bytecode for `Vm`.
+///
+/// The `Resolver` has already rejected invalid programs, so the only errors
+/// left here are the VM's own limits (locals, upvalues, constants, jumps).
+#[derive(Debug)]
+pub struct Compiler {
+    states: Vec<FunctionState>,
+    line: usize,
+    had_error: Cell<bool>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            states: vec![],
+            line: 1,
+            had_error: Cell::new(false),
+        }
+    }
+
+    pub fn compile(mut self, statements: &[Stmt]) -> Result<Rc<Function>, LoxError> {
+        self.states
+            .push(FunctionState::new(FunctionKind::Script, None));
+
+        for statement in statements {
+            self.compile_stmt(statement);
+        }
+
+        self.finish()
+    }
+
+    /// Compiles a lone expression into a script that returns its value, for
+    /// `Vm::evaluate`.
+    pub fn compile_expression(mut self, expr: &Expr) -> Result<Rc<Function>, LoxError> {
+        self.states
+            .push(FunctionState::new(FunctionKind::Script, None));
+
+        self.compile_expr(expr);
+        self.emit(Op::Return);
+
+        self.finish()
+    }
+
+    fn finish(mut self) -> Result<Rc<Function>, LoxError> {
+        let function = self.end_function();
+
+        if self.had_error.get() {
+            return Err(LoxError::CompileError);
+        }
+
+        Ok(Rc::new(function))
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) {
+        stmt.accept(self)
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state().function.chunk
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let line = self.line;
+        self.chunk().write(op, line)
+    }
+
+    fn at(&mut self, token: &Token) -> &mut Self {
+        self.line = token.line;
+        self
+    }
+
+    fn error(&self, token: &Token, message: &str) {
+        error::lox_error_token(token, message);
+        self.had_error.set(true);
+    }
+
+    fn make_constant(&mut self, value: Value, token: &Token) -> u16 {
+        let index = self.chunk().add_constant(value);
+        if index > u16::MAX as usize {
+            self.error(token, "Too many constants in one chunk.");
+            return 0;
+        }
+        index as u16
+    }
+
+    fn identifier_constant(&mut self, name: &Token) -> u16 {
+        self.make_constant(Value::Str(name.lexeme.as_str().into()), name)
+    }
+
+    fn emit_jump(&mut self, op: fn(u16) -> Op) -> usize {
+        self.emit(op(u16::MAX))
+    }
+
+    fn patch_jump(&mut self, index: usize) {
+        let offset = self.chunk().code.len() - index - 1;
+        let offset = match u16::try_from(offset) {
+            Ok(offset) => offset,
+            Err(_) => {
+                let token = self.here();
+                self.error(&token, "Too much code to jump over.");
+                0
+            }
+        };
+
+        let chunk = self.chunk();
+        chunk.code[index] = match chunk.code[index] {
+            Op::Jump(_) => Op::Jump(offset),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(offset),
+            op => unreachable!("{:?} is not a forward jump.", op),
+        };
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        let offset = self.chunk().code.len() - loop_start + 1;
+        let offset = match u16::try_from(offset) {
+            Ok(offset) => offset,
+            Err(_) => {
+                let token = self.here();
+                self.error(&token, "Loop body too large.");
+                0
+            }
+        };
+        self.emit(Op::Loop(offset));
+    }
+
+    // A token pointing at the current line, for errors with no better location.
+    fn here(&self) -> Token {
+        Token::new(TokenType::Eof, String::new(), Object::Null, self.line)
+    }
+
+    fn emit_return(&mut self) {
+        if self.state().kind == FunctionKind::Initializer {
+            self.emit(Op::GetLocal(0));
+        } else {
+            self.emit(Op::Nil);
+        }
+        self.emit(Op::Return);
+    }
+
+    fn end_function(&mut self) -> Function {
+        self.emit_return();
+        let state = self.states.pop().unwrap();
+        Function {
+            upvalues: state.upvalues,
+            ..state.function
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.state().scope_depth -= 1;
+
+        loop {
+            let state = self.state();
+            let captured = match state.locals.last() {
+                Some(local) if local.depth > state.scope_depth => local.is_captured,
+                _ => break,
+            };
+            state.locals.pop();
+
+            if captured {
+                self.emit(Op::CloseUpvalue);
+            } else {
+                self.emit(Op::Pop);
+            }
+        }
+    }
+
+    fn add_local(&mut self, name: &Token) {
+        if self.state().locals.len() == MAX_LOCALS {
+            self.error(name, "Too many local variables in function.");
+            return;
+        }
+
+        let depth = self.state().scope_depth;
+        self.state().locals.push(Local {
+            name: name.lexeme.clone(),
+            depth,
+            is_captured: false,
+        });
+    }
+
+    // Declares `name` in the current scope, or returns the global's constant
+    // index when we are at the top level.
+    fn declare_variable(&mut self, name: &Token) -> Option<u16> {
+        if self.state().scope_depth == 0 {
+            return Some(self.identifier_constant(name));
+        }
+
+        self.add_local(name);
+        None
+    }
+
+    fn define_variable(&mut self, global: Option<u16>) {
+        if let Some(global) = global {
+            self.emit(Op::DefineGlobal(global));
+        }
+    }
+
+    fn resolve_local(&self, state: usize, name: &str) -> Option<u8> {
+        self.states[state]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|index| index as u8)
+    }
+
+    fn resolve_upvalue(&mut self, state: usize, name: &Token) -> Option<u8> {
+        if state == 0 {
+            return None;
+        }
+
+        if let Some(local) = self.resolve_local(state - 1, &name.lexeme) {
+            self.states[state - 1].locals[local as usize].is_captured = true;
+            return Some(self.add_upvalue(state, local, true, name));
+        }
+
+        if let Some(upvalue) = self.resolve_upvalue(state - 1, name) {
+            return Some(self.add_upvalue(state, upvalue, false, name));
+        }
+
+        None
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: u8, is_local: bool, name: &Token) -> u8 {
+        let upvalue = UpvalueDesc { is_local, index };
+        let upvalues = &mut self.states[state].upvalues;
+
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+
+        if upvalues.len() == MAX_UPVALUES {
+            self.error(name, "Too many closure variables in function.");
+            return 0;
+        }
+
+        upvalues.push(upvalue);
+        (upvalues.len() - 1) as u8
+    }
+
+    fn named_variable(&mut self, name: &Token, assign: Option<&Expr>) {
+        self.line = name.line;
+        let current = self.states.len() - 1;
+
+        let (get, set) = if let Some(slot) = self.resolve_local(current, &name.lexeme) {
+            (Op::GetLocal(slot), Op::SetLocal(slot))
+        } else if let Some(slot) = self.resolve_upvalue(current, name) {
+            (Op::GetUpvalue(slot), Op::SetUpvalue(slot))
+        } else {
+            let global = self.identifier_constant(name);
+            (Op::GetGlobal(global), Op::SetGlobal(global))
+        };
+
+        match assign {
+            Some(value) => {
+                self.compile_expr(value);
+                self.at(name).emit(set);
+            }
+            None => {
+                self.emit(get);
+            }
+        }
+    }
+
+    fn function(&mut self, declaration: &StmtFunction, kind: FunctionKind) {
+        let name: Rc<str> = declaration.name.lexeme.as_str().into();
+        self.states.push(FunctionState::new(kind, Some(name)));
+        self.begin_scope();
+
+        self.state().function.arity = declaration.params.len();
+        for param in &declaration.params {
+            self.add_local(param);
+        }
+
+        // Parameters and body share one scope, as in the tree-walker.
+        for statement in &declaration.body {
+            self.compile_stmt(statement);
+        }
+
+        let function = self.end_function();
+        let constant = self.make_constant(Value::Function(Rc::new(function)), &declaration.name);
+        self.at(&declaration.name).emit(Op::Closure(constant));
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl StmtVisitor<()> for Compiler {
+    fn visit_expression_stmt(&mut self, stmt: &StmtExpression) {
+        self.compile_expr(&stmt.expression);
+        self.emit(Op::Pop);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &StmtPrint) {
+        self.compile_expr(&stmt.expression);
+        self.emit(Op::Print);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &StmtVar) {
+        let global = if self.state().scope_depth == 0 {
+            Some(self.identifier_constant(&stmt.name))
+        } else {
+            None
+        };
+
+        match stmt.initializer {
+            Some(ref initializer) => self.compile_expr(initializer),
+            None => {
+                self.at(&stmt.name).emit(Op::Nil);
+            }
+        }
+
+        // A local only comes into scope once its initializer has been compiled.
+        if global.is_none() {
+            self.add_local(&stmt.name);
+        }
+        self.at(&stmt.name).define_variable(global);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &StmtBlock) {
+        self.begin_scope();
+        for statement in &stmt.statements {
+            self.compile_stmt(statement);
+        }
+        self.end_scope();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &StmtIf) {
+        self.compile_expr(&stmt.condition);
+
+        let then_jump = self.emit_jump(Op::JumpIfFalse);
+        self.emit(Op::Pop);
+        self.compile_stmt(&stmt.then_branch);
+
+        let else_jump = self.emit_jump(Op::Jump);
+        self.patch_jump(then_jump);
+        self.emit(Op::Pop);
+
+        if let Some(ref else_branch) = stmt.else_branch {
+            self.compile_stmt(else_branch);
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &StmtWhile) {
+        let loop_start = self.chunk().code.len();
+        self.compile_expr(&stmt.condition);
+
+        let exit_jump = self.emit_jump(Op::JumpIfFalse);
+        self.emit(Op::Pop);
+        self.compile_stmt(&stmt.body);
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit(Op::Pop);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<StmtFunction>) {
+        // Declared before the body is compiled so the function can recurse.
+        let global = self.declare_variable(&stmt.name);
+        self.function(stmt, FunctionKind::Function);
+        self.define_variable(global);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &StmtReturn) {
+        self.line = stmt.keyword.line;
+
+        match stmt.value {
+            Some(ref value) => {
+                self.compile_expr(value);
+                self.at(&stmt.keyword).emit(Op::Return);
+            }
+            None => self.emit_return(),
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &StmtClass) {
+        let name = self.identifier_constant(&stmt.name);
+        let global = self.declare_variable(&stmt.name);
+
+        self.at(&stmt.name).emit(Op::Class(name));
+        self.define_variable(global);
+
+        if let Some(ref superclass) = stmt.superclass {
+            self.compile_expr(superclass);
+
+            self.begin_scope();
+            let super_token = Token::new(
+                TokenType::Super,
+                "super".into(),
+                Object::Null,
+                stmt.name.line,
+            );
+            self.add_local(&super_token);
+
+            self.named_variable(&stmt.name, None);
+            if let Expr::Variable(ref variable) = superclass {
+                self.line = variable.name.line;
+            }
+            self.emit(Op::Inherit);
+        }
+
+        self.named_variable(&stmt.name, None);
+
+        for method in &stmt.methods {
+            let function = match method {
+                Stmt::Function(function) => function,
+                _ => panic!("StmtClass.methods must contain StmtFunction only."),
+            };
+
+            let kind = if function.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+
+            let method_name = self.identifier_constant(&function.name);
+            self.function(function, kind);
+            self.emit(Op::Method(method_name));
+        }
+
+        self.emit(Op::Pop);
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+    }
+}
+
+impl ExprVisitor<()> for Compiler {
+    fn visit_literal_expr(&mut self, expr: &ExprLiteral) {
+        match expr.value {
+            Object::Null => self.emit(Op::Nil),
+            Object::Bool(true) => self.emit(Op::True),
+            Object::Bool(false) => self.emit(Op::False),
+            Object::Num(value) => {
+                let token = self.here();
+                let constant = self.make_constant(Value::Num(value), &token);
+                self.emit(Op::Constant(constant))
+            }
+            Object::Str(ref value) => {
+                let token = self.here();
+                let constant = self.make_constant(Value::Str(value.as_str().into()), &token);
+                self.emit(Op::Constant(constant))
+            }
+            _ => unreachable!("Only primitive values appear as literals."),
+        };
+    }
+
+    fn visit_unary_expr(&mut self, expr: &ExprUnary) {
+        self.compile_expr(&expr.right);
+
+        let op = match expr.operator.typ {
+            TokenType::Bang => Op::Not,
+            TokenType::Minus => Op::Negate,
+            _ => unreachable!(),
+        };
+        self.at(&expr.operator).emit(op);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &ExprBinary) {
+        self.compile_expr(&expr.left);
+        self.compile_expr(&expr.right);
+
+        let op = match expr.operator.typ {
+            TokenType::Greater => Op::Greater,
+            TokenType::GreaterEqual => Op::GreaterEqual,
+            TokenType::Less => Op::Less,
+            TokenType::LessEqual => Op::LessEqual,
+            TokenType::BangEqual => Op::NotEqual,
+            TokenType::EqualEqual => Op::Equal,
+            TokenType::Minus => Op::Subtract,
+            TokenType::Plus => Op::Add,
+            TokenType::Slash => Op::Divide,
+            TokenType::Star => Op::Multiply,
+            _ => unreachable!(),
+        };
+        self.at(&expr.operator).emit(op);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &ExprGrouping) {
+        self.compile_expr(&expr.expression);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &ExprVariable) {
+        self.named_variable(&expr.name, None);
+    }
+
+    fn visit_assign_expr(&mut self, expr: &ExprAssign) {
+        self.named_variable(&expr.name, Some(&expr.value));
+    }
+
+    fn visit_logical_expr(&mut self, expr: &ExprLogical) {
+        self.compile_expr(&expr.left);
+        self.line = expr.operator.line;
+
+        if expr.operator.typ == TokenType::Or {
+            let else_jump = self.emit_jump(Op::JumpIfFalse);
+            let end_jump = self.emit_jump(Op::Jump);
+
+            self.patch_jump(else_jump);
+            self.emit(Op::Pop);
+            self.compile_expr(&expr.right);
+            self.patch_jump(end_jump);
+        } else {
+            let end_jump = self.emit_jump(Op::JumpIfFalse);
+
+            self.emit(Op::Pop);
+            self.compile_expr(&expr.right);
+            self.patch_jump(end_jump);
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &ExprCall) {
+        let argc = expr.arguments.len() as u8;
+
+        match *expr.callee {
+            Expr::Get(ref get) => {
+                self.compile_expr(&get.object);
+                let name = self.identifier_constant(&get.name);
+                for argument in &expr.arguments {
+                    self.compile_expr(argument);
+                }
+                self.at(&expr.paren).emit(Op::Invoke(name, argc));
+            }
+            Expr::Super(ref sup) => {
+                let this = Token::new(
+                    TokenType::This,
+                    "this".into(),
+                    Object::Null,
+                    sup.keyword.line,
+                );
+                let name = self.identifier_constant(&sup.method);
+                self.named_variable(&this, None);
+                for argument in &expr.arguments {
+                    self.compile_expr(argument);
+                }
+                self.named_variable(&sup.keyword, None);
+                self.at(&expr.paren).emit(Op::SuperInvoke(name, argc));
+            }
+            ref callee => {
+                self.compile_expr(callee);
+                for argument in &expr.arguments {
+                    self.compile_expr(argument);
+                }
+                self.at(&expr.paren).emit(Op::Call(argc));
+            }
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &ExprGet) {
+        self.compile_expr(&expr.object);
+        let name = self.identifier_constant(&expr.name);
+        self.at(&expr.name).emit(Op::GetProperty(name));
+    }
+
+    fn visit_set_expr(&mut self, expr: &ExprSet) {
+        self.compile_expr(&expr.object);
+        self.compile_expr(&expr.value);
+        let name = self.identifier_constant(&expr.name);
+        self.at(&expr.name).emit(Op::SetProperty(name));
+    }
+
+    fn visit_this_expr(&mut self, expr: &ExprThis) {
+        self.named_variable(&expr.keyword, None);
+    }
+
+    fn visit_super_expr(&mut self, expr: &ExprSuper) {
+        let this = Token::new(
+            TokenType::This,
+            "this".into(),
+            Object::Null,
+            expr.keyword.line,
+        );
+        let name = self.identifier_constant(&expr.method);
+
+        self.named_variable(&this, None);
+        self.named_variable(&expr.keyword, None);
+        self.at(&expr.method).emit(Op::GetSuper(name));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug)]
+struct Local {
+    name: String,
+    depth: usize,
+    is_captured: bool,
+}
+
+#[derive(Debug)]
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueDesc>,
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(kind: FunctionKind, name: Option<Rc<str>>) -> Self {
+        // Slot zero holds the callee, or the receiver inside methods.
+        let slot_zero = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+
+        Self {
+            function: Function {
+                name,
+                ..Function::default()
+            },
+            kind,
+            locals: vec![Local {
+                name: slot_zero.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: vec![],
+            scope_depth: 0,
+        }
+    }
+}
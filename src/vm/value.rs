@@ -0,0 +1,151 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{chunk::Chunk, Vm};
+use crate::error::LoxError;
+
+/// A runtime value of the bytecode VM.
+///
+/// Strings are immutable and shared; everything with identity lives behind an
+/// `Rc` and compares by pointer, like objects in the book's clox.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Bool(bool),
+    Num(f64),
+    Str(Rc<str>),
+    Nil,
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    Native(Rc<Native>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    BoundMethod(Rc<BoundMethod>),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// The same names `Object::type_name` gives the tree-walker's values.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "boolean",
+            Value::Num(_) => "number",
+            Value::Str(_) => "string",
+            Value::Nil => "nil",
+            Value::Function(_) | Value::Closure(_) | Value::BoundMethod(_) => "function",
+            Value::Native(_) => "native function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
+
+    // Constants are deduplicated by value, but a `-0` must not be folded into
+    // an existing `0` (they print differently).
+    pub(super) fn same_constant(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Num(a), Value::Num(b)) => a.to_bits() == b.to_bits(),
+            (Value::Str(a), Value::Str(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Num(a), Value::Num(b)) if a.is_nan() && b.is_nan() => true,
+            (Value::Num(a), Value::Num(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::BoundMethod(a), Value::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Num(v) => write!(f, "{}", v),
+            Value::Str(v) => write!(f, "{}", v),
+            Value::Nil => write!(f, "nil"),
+            Value::Function(v) => write!(f, "{}", v),
+            Value::Closure(v) => write!(f, "{}", v.function),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(v) => write!(f, "{}", v.name),
+            Value::Instance(v) => write!(f, "{} instance", v.class.name),
+            Value::BoundMethod(v) => write!(f, "{}", v.method.function),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpvalueDesc {
+    pub is_local: bool,
+    pub index: u8,
+}
+
+/// A compiled function body. The top-level script is a function without a name.
+#[derive(Debug, Default)]
+pub struct Function {
+    pub name: Option<Rc<str>>,
+    pub arity: usize,
+    pub chunk: Chunk,
+    pub upvalues: Vec<UpvalueDesc>,
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<script>"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Upvalue {
+    /// The captured variable still lives on the VM stack at this slot.
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+pub type NativeFn = fn(&mut Vm, &[Value]) -> Result<Value, LoxError>;
+
+#[derive(Debug)]
+pub struct Native {
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: Rc<str>,
+    pub methods: RefCell<HashMap<Rc<str>, Rc<Closure>>>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: RefCell<HashMap<Rc<str>, Value>>,
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
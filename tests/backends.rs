@@ -0,0 +1,36 @@
+use std::{path::Path, process::Command};
+
+fn run(args: &[&str], script: &Path) -> (String, String, Option<i32>) {
+    let output = Command::new(env!("CARGO_BIN_EXE_jlox-rs"))
+        .args(args)
+        .arg(script)
+        .output()
+        .expect("failed to run jlox");
+
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.code(),
+    )
+}
+
+#[test]
+fn vm_matches_tree_walker_on_examples() {
+    let mut scripts: Vec<_> = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/examples"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    scripts.sort();
+
+    assert!(!scripts.is_empty());
+
+    for script in &scripts {
+        assert_eq!(
+            run(&[], script),
+            run(&["--vm"], script),
+            "backends disagree on {}",
+            script.display()
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+fn jlox(args: &[&str]) -> (String, String, Option<i32>) {
+    jlox_with_stdin(args, "")
+}
+
+fn jlox_with_stdin(args: &[&str], stdin: &str) -> (String, String, Option<i32>) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jlox-rs"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run jlox");
+    // jlox may exit before it reads everything, e.g. on a usage error.
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    let output = child.wait_with_output().unwrap();
+
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.code(),
+    )
+}
+
+#[test]
+fn evaluates_inline_code() {
+    let (stdout, _, status) = jlox(&["-e", "var a = 2; print a * 3;"]);
+    assert_eq!((stdout.as_str(), status), ("6\n", Some(0)));
+
+    let (stdout, _, status) = jlox(&["--vm", "-e", "print \"a\" + \"b\";"]);
+    assert_eq!((stdout.as_str(), status), ("ab\n", Some(0)));
+}
+
+#[test]
+fn dumps_without_running() {
+    let (stdout, _, status) = jlox(&["--tokens", "-e", "print 1;"]);
+    assert_eq!(status, Some(0));
+    assert_eq!(stdout.lines().count(), 4);
+
+    let (stdout, _, _) = jlox(&["--ast", "-e", "fun f(a) { return a + 1; } print f(1);"]);
+    assert_eq!(
+        stdout,
+        "(fun f (a)\n  (return (+ a 1)))\n(print (call f 1))\n"
+    );
+
+    let (stdout, _, _) = jlox(&["--ast-json", "-e", "print x;"]);
+    assert!(stdout.starts_with("{\n  \"type\": \"Program\""));
+    assert!(stdout.contains("\"lexeme\": \"x\",\n"));
+
+    let (stdout, _, _) = jlox(&["--dot-calls", "-e", "fun f() { g(); } fun g() {} f();"]);
+    assert!(stdout.contains("  \"f\" -> \"g\";\n  \"<script>\" -> \"f\";\n"));
+
+    let (stdout, _, _) = jlox(&["--resolved", "-e", "{ var a = 1; print a; } print a;"]);
+    assert_eq!(stdout, "[line 1] a: local, depth 0\n[line 1] a: global\n");
+}
+
+#[test]
+fn check_reports_static_errors_only() {
+    let (stdout, _, status) = jlox(&["--check", "-e", "print 1; print nil + 1;"]);
+    assert_eq!((stdout.as_str(), status), ("", Some(0)));
+
+    let (_, stderr, status) = jlox(&["--check", "-e", "return 1;"]);
+    assert_eq!(status, Some(65));
+    assert!(stderr.contains("Can't return from top-level code."));
+}
+
+#[test]
+fn rejects_bad_usage() {
+    assert_eq!(jlox(&["-e", "print 1;", "extra"]).2, Some(64));
+    assert_eq!(jlox(&["--ast", "-e"]).2, Some(64));
+}
+
+#[test]
+fn passes_arguments_and_exit_status_to_scripts() {
+    let script = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scripts/args.lox");
+
+    for backend in [&[][..], &["--vm"][..]] {
+        let mut args = backend.to_vec();
+        args.extend([script, "one", "--two"]);
+
+        let (stdout, _, status) = jlox(&args);
+        assert_eq!(stdout, format!("3\n{}\none\n--two\nnil\n", script));
+        assert_eq!(status, Some(7));
+    }
+}
+
+#[test]
+fn runs_programs_from_stdin() {
+    let program = "#!/usr/bin/env jlox\nprint argv(0) + argv(1);\nprint nil + 1;\n";
+
+    for args in [&["-", "!"][..], &["--vm", "-", "!"][..]] {
+        let (stdout, stderr, status) = jlox_with_stdin(args, program);
+        assert_eq!(stdout, "-!\n");
+        assert!(stderr.ends_with("[line 3]\n"));
+        assert_eq!(status, Some(70));
+    }
+
+    // Piped input is a program, not a REPL session.
+    let (stdout, _, status) = jlox_with_stdin(&[], "var a = 1;\nprint a + 1;\n");
+    assert_eq!((stdout.as_str(), status), ("2\n", Some(0)));
+}
+
+#[test]
+fn lints_with_selectable_output() {
+    let program = "fun f() { var a; }\nprint f();\n";
+
+    let (stdout, _, status) = jlox_with_stdin(&["lint"], program);
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        stdout,
+        "-:1: Local variable 'a' is never read. [unused-variable]\n\
+         -:2: 'f' never returns a value, so this call is always nil. [no-return-value]\n"
+    );
+
+    let (stdout, _, _) = jlox_with_stdin(&["lint", "--format", "json"], program);
+    assert!(stdout.contains("\"rule\": \"no-return-value\""));
+
+    let (stdout, _, status) = jlox_with_stdin(&["lint"], "print 1;\n");
+    assert_eq!((stdout.as_str(), status), ("", Some(0)));
+}
+
+#[test]
+fn profiles_calls() {
+    let program =
+        "fun f(n) { if (n > 0) f(n - 1); }\nclass A { init() { f(2); } }\nA();\nclock();\n";
+    let folded = std::env::temp_dir().join(format!("jlox-profile-{}.folded", std::process::id()));
+    let profile = format!("--profile={}", folded.display());
+
+    let (_, stderr, status) = jlox(&[&profile, "-e", program]);
+    assert_eq!(status, Some(0));
+    let calls: Vec<(&str, &str)> = stderr
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let mut columns = line.split_whitespace();
+            (columns.next().unwrap(), columns.next().unwrap())
+        })
+        .collect();
+    for row in [
+        ("f", "3"),
+        ("A", "1"),
+        ("init", "1"),
+        ("clock", "1"),
+        ("<script>", "1"),
+    ] {
+        assert!(calls.contains(&row), "{:?} not in {}", row, stderr);
+    }
+
+    let stacks = std::fs::read_to_string(&folded).unwrap();
+    std::fs::remove_file(&folded).unwrap();
+    assert!(stacks.contains("\n<script>;A;init;f;f;f "), "{}", stacks);
+
+    assert_eq!(jlox(&["--profile", "--vm", "-e", program]).2, Some(64));
+}
+
+#[test]
+fn writes_lcov_coverage() {
+    let program = "fun f(n) {\n  if (n > 1 or n < -1)\n    return 1;\n  return 0;\n}\nf(5);\nif (false) {\n  print 1;\n}\nfun g(a) { return a and a; }\n";
+    let lcov = std::env::temp_dir().join(format!("jlox-coverage-{}.info", std::process::id()));
+    let coverage = format!("--coverage={}", lcov.display());
+
+    assert_eq!(jlox(&[&coverage, "-e", program]).2, Some(0));
+    let report = std::fs::read_to_string(&lcov).unwrap();
+    std::fs::remove_file(&lcov).unwrap();
+    assert_eq!(
+        report,
+        "TN:\nSF:-e\n\
+         BRDA:2,0,0,1\nBRDA:2,0,1,0\nBRDA:2,1,0,0\nBRDA:2,1,1,1\n\
+         BRDA:7,2,0,0\nBRDA:7,2,1,1\nBRDA:10,3,0,-\nBRDA:10,3,1,-\n\
+         BRF:8\nBRH:3\n\
+         DA:1,1\nDA:2,1\nDA:3,1\nDA:4,0\nDA:6,1\nDA:7,1\nDA:8,0\nDA:10,1\n\
+         LF:8\nLH:6\nend_of_record\n"
+    );
+}
+
+#[test]
+fn runs_lox_tests() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scripts/tests");
+    let (stdout, _, status) = jlox(&["test", dir]);
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        stdout.replace(dir, "tests"),
+        "test tests/math_test.lox::test_square ... ok\n\
+         test tests/math_test.lox::test_wrong ... FAILED\n\
+         test tests/math_test.lox::test_error ... FAILED\n\
+         test tests/string_test.lox::test_concat ... ok\n\
+         \n\
+         failures:\n    \
+         tests/math_test.lox::test_wrong: Assertion failed: 2 squared is 4 [line 10]\n    \
+         tests/math_test.lox::test_error: Operands must be numbers. [line 2]\n\
+         \n\
+         test result: FAILED. 2 passed; 2 failed\n"
+    );
+
+    let file = format!("{}/string_test.lox", dir);
+    assert_eq!(jlox(&["test", &file]).2, Some(0));
+
+    let (_, stderr, status) = jlox(&["-e", "assert(1 < 2, \"fine\");\nassert(nil, \"nope\");"]);
+    assert_eq!(status, Some(70));
+    assert_eq!(stderr, "Assertion failed: nope\n[line 2]\n");
+}
+
+#[test]
+fn traces_execution() {
+    let program = "fun inc(n) {\n  return n + 1;\n}\nfun two() { return inc(inc(0)); }\nvar a = \"x\";\na = two();\n";
+
+    let (stdout, stderr, status) = jlox(&["--trace", "-e", program]);
+    assert_eq!((stdout.as_str(), status), ("", Some(0)));
+    assert_eq!(
+        stderr,
+        "[line 1] fun inc(n) {\n\
+         [line 4] fun two() { return inc(inc(0)); }\n\
+         [line 5] var a = \"x\";\n\
+         define a = \"x\"\n\
+         [line 6] a = two();\n\
+         call two()\n\
+         \x20 [line 4] fun two() { return inc(inc(0)); }\n\
+         \x20 call inc(0)\n\
+         \x20   [line 2] return n + 1;\n\
+         \x20 inc returned 1\n\
+         \x20 call inc(1)\n\
+         \x20   [line 2] return n + 1;\n\
+         \x20 inc returned 2\n\
+         two returned 2\n\
+         assign a = 2\n"
+    );
+
+    let (_, stderr, _) = jlox(&["--trace", "--trace-fn", "inc", "-e", program]);
+    assert_eq!(
+        stderr,
+        "call inc(0)\n  [line 2] return n + 1;\ninc returned 1\n\
+         call inc(1)\n  [line 2] return n + 1;\ninc returned 2\n"
+    );
+}
+
+#[test]
+fn traces_statements_the_optimizer_would_remove() {
+    let program = "if (true) {\n  print 1;\n}\nwhile (false) print 2;\n";
+
+    let (stdout, stderr, _) = jlox(&["--trace", "-e", program]);
+    assert_eq!(stdout, "1\n");
+    assert_eq!(
+        stderr,
+        "[line 1] if (true) {\n\
+         [line 2] print 1;\n\
+         [line 4] while (false) print 2;\n"
+    );
+}
+
+#[test]
+fn debugs_with_commands_from_stdin() {
+    let program = "var a = 1;\nfun f(n) {\n  var b = n + 1;\n  return b;\n}\nprint f(a);\n";
+    let commands = "break 4\ncontinue\nbacktrace\nprint b = b * 10\nnext\n";
+
+    let (stdout, _, status) = jlox_with_stdin(&["--debug", "-e", program], commands);
+    assert_eq!(status, Some(0));
+    assert_eq!(
+        stdout,
+        "[line 1] var a = 1;\n\
+         (jlox) Breakpoint at line 4.\n\
+         (jlox) [line 4 in f] return b;\n\
+         (jlox) #0 f at line 4\n#1 <script> at line 6\n\
+         (jlox) 20\n\
+         (jlox) 20\n"
+    );
+
+    // Statements the optimizer would fold away still stop.
+    let program = "var a = 1;\nif (true) {\n  a = 2;\n}\nwhile (false) a = 3;\nprint a;\n";
+    let (stdout, _, _) = jlox_with_stdin(
+        &["--debug", "-e", program],
+        "break 2\nbreak 5\ncontinue\ncontinue\ncontinue\n",
+    );
+    assert_eq!(
+        stdout,
+        "[line 1] var a = 1;\n\
+         (jlox) Breakpoint at line 2.\n\
+         (jlox) Breakpoint at line 5.\n\
+         (jlox) [line 2] if (true) {\n\
+         (jlox) [line 5] while (false) a = 3;\n\
+         (jlox) 2\n"
+    );
+
+    // The commands are read from stdin, so the program cannot be.
+    assert_eq!(jlox_with_stdin(&["--debug"], program).2, Some(64));
+    assert_eq!(jlox(&["--debug", "--vm", "-e", program]).2, Some(64));
+}
@@ -0,0 +1,225 @@
+use std::{
+    collections::VecDeque,
+    io::BufReader,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use jlox_rs::lsp::{read_message, write_message};
+use serde_json::{json, Value};
+
+const PROGRAM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scripts/point.lox");
+
+/// Talks to `jlox-dap` the way an editor would.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    seq: u64,
+    /// Events that arrived while waiting for a response.
+    events: VecDeque<Value>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jlox-dap"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to run jlox-dap");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Self {
+            child,
+            stdin,
+            stdout,
+            seq: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let seq = self.seq;
+        let message = json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        });
+        write_message(&mut self.stdin, &message).unwrap();
+
+        loop {
+            let message = self.receive();
+            if message["type"] == "event" {
+                self.events.push_back(message);
+                continue;
+            }
+            assert_eq!(message["request_seq"], seq);
+            assert_eq!(message["success"], true, "{}", message);
+            return message["body"].clone();
+        }
+    }
+
+    fn receive(&mut self) -> Value {
+        read_message(&mut self.stdout)
+            .unwrap()
+            .expect("adapter hung up")
+    }
+
+    /// Waits for `event`, skipping any others but program output.
+    fn event(&mut self, event: &str) -> Value {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => self.receive(),
+            };
+            if message["event"] == event {
+                return message["body"].clone();
+            }
+            assert_ne!(message["event"], "output", "unexpected {}", message);
+        }
+    }
+
+    fn output(&mut self) -> String {
+        self.event("output")["output"].as_str().unwrap().to_string()
+    }
+
+    fn stopped_at(&mut self) -> (String, Value, Value) {
+        let reason = self.event("stopped")["reason"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let frames = self.request("stackTrace", json!({ "threadId": 1 }))["stackFrames"].clone();
+        (reason, frames[0]["name"].clone(), frames[0]["line"].clone())
+    }
+
+    /// The variables under `reference`, as `name = value`.
+    fn variables(&mut self, reference: &Value) -> Vec<String> {
+        let body = self.request("variables", json!({ "variablesReference": reference }));
+        body["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| {
+                format!(
+                    "{} = {}",
+                    v["name"].as_str().unwrap(),
+                    v["value"].as_str().unwrap()
+                )
+            })
+            .collect()
+    }
+
+    fn launch(&mut self, breakpoints: &[u64], stop_on_entry: bool) {
+        let capabilities = self.request("initialize", json!({ "adapterID": "jlox" }));
+        assert_eq!(capabilities["supportsConfigurationDoneRequest"], true);
+        self.event("initialized");
+
+        self.request(
+            "launch",
+            json!({ "program": PROGRAM, "stopOnEntry": stop_on_entry }),
+        );
+        let lines: Vec<Value> = breakpoints
+            .iter()
+            .map(|line| json!({ "line": line }))
+            .collect();
+        let body = self.request(
+            "setBreakpoints",
+            json!({ "source": { "path": PROGRAM }, "breakpoints": lines }),
+        );
+        assert_eq!(
+            body["breakpoints"].as_array().unwrap().len(),
+            breakpoints.len()
+        );
+        self.request("configurationDone", json!({}));
+    }
+
+    fn finish(mut self, status: i64) {
+        assert_eq!(self.event("exited")["exitCode"], status);
+        self.event("terminated");
+        self.request("disconnect", json!({}));
+        assert_eq!(self.child.wait().unwrap().code(), Some(0));
+    }
+}
+
+#[test]
+fn stops_at_breakpoints_and_shows_variables() {
+    let mut client = Client::start();
+    client.launch(&[10], false);
+
+    assert_eq!(
+        client.stopped_at(),
+        ("breakpoint".to_string(), json!("norm"), json!(10))
+    );
+    let threads = client.request("threads", json!({}));
+    assert_eq!(threads["threads"][0]["id"], 1);
+
+    let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(trace["stackFrames"][1]["name"], "<script>");
+    assert_eq!(trace["stackFrames"][1]["line"], 15);
+    assert!(trace["stackFrames"][0]["source"]["path"]
+        .as_str()
+        .unwrap()
+        .ends_with("point.lox"));
+
+    let scopes = client.request("scopes", json!({ "frameId": 0 }));
+    let locals = scopes["scopes"][0]["variablesReference"].clone();
+    assert_eq!(
+        client.variables(&locals),
+        vec!["point = Point instance", "squared = 25"]
+    );
+
+    // Instances expand into their fields.
+    let body = client.request("variables", json!({ "variablesReference": locals }));
+    let point = body["variables"][0]["variablesReference"].clone();
+    assert_eq!(client.variables(&point), vec!["x = 3", "y = 4"]);
+
+    let body = client.request(
+        "evaluate",
+        json!({ "expression": "squared + point.x", "frameId": 0 }),
+    );
+    assert_eq!(body["result"], "28");
+    let body = client.request("evaluate", json!({ "expression": "p", "frameId": 1 }));
+    assert_eq!(body["result"], "Point instance");
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.output(), "25\n");
+    assert_eq!(client.stopped_at().2, json!(10));
+
+    client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": PROGRAM }, "breakpoints": [] }),
+    );
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.output(), "0\n");
+    client.finish(0);
+}
+
+#[test]
+fn steps_in_over_and_out() {
+    let mut client = Client::start();
+    client.launch(&[], true);
+
+    assert_eq!(
+        client.stopped_at(),
+        ("entry".to_string(), json!("<script>"), json!(1))
+    );
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped_at().2, json!(8));
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped_at().2, json!(13));
+
+    client.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(
+        client.stopped_at(),
+        ("step".to_string(), json!("init"), json!(3))
+    );
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped_at().2, json!(14));
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.output(), "25\n");
+    assert_eq!(client.output(), "0\n");
+    client.finish(0);
+}
@@ -0,0 +1,60 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use jlox_rs::formatter::format;
+
+#[test]
+fn formatting_examples_is_idempotent_and_keeps_meaning() {
+    let mut scripts: Vec<_> = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/examples"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    scripts.sort();
+
+    assert!(!scripts.is_empty());
+
+    for script in &scripts {
+        let source = std::fs::read_to_string(script).unwrap();
+        let formatted = format(&source).unwrap();
+
+        assert_eq!(
+            format(&formatted).unwrap(),
+            formatted,
+            "{}",
+            script.display()
+        );
+        assert_eq!(
+            jlox_rs::dump_ast(&formatted).unwrap(),
+            jlox_rs::dump_ast(&source).unwrap(),
+            "{}",
+            script.display()
+        );
+    }
+}
+
+#[test]
+fn check_mode_fails_on_unformatted_input() {
+    let check = |source: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jlox-rs"))
+            .args(["fmt", "--check"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(source.as_bytes())
+            .unwrap();
+        child.wait().unwrap().code()
+    };
+
+    assert_eq!(check("print 1 + 2;\n"), Some(0));
+    assert_eq!(check("print 1+2;\n"), Some(1));
+    assert_eq!(check("print (;\n"), Some(65));
+}
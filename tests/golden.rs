@@ -0,0 +1,162 @@
+//! Runs every `.lox` file under `tests/lox` and `examples` on the
+//! tree-walker, in closure mode and on the VM, and checks it against the annotations in its comments, in the format of
+//! the Crafting Interpreters test suite:
+//!
+//! - `// expect: OUTPUT` is the next line the program prints.
+//! - `// expect runtime error: MESSAGE` is the runtime error the program
+//!   stops with, on the comment's line; the exit status must be 70.
+//! - `// Error at 'x': MESSAGE` is a static error on the comment's line, and
+//!   `// [line N] Error...` one on line N; the exit status must be 65.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const DIRS: [&str; 2] = ["tests/lox", "examples"];
+
+#[derive(Debug, Default)]
+struct Expectations {
+    output: Vec<String>,
+    errors: Vec<String>,
+    runtime_error: Option<String>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Self {
+        let mut expectations = Self::default();
+
+        for (index, line) in source.lines().enumerate() {
+            let Some((_, comment)) = line.split_once("// ") else {
+                continue;
+            };
+            let line = index + 1;
+
+            if let Some(output) = comment.strip_prefix("expect: ") {
+                expectations.output.push(output.to_string());
+            } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+                expectations.runtime_error = Some(format!("{}\n[line {}]", message, line));
+            } else if comment.starts_with("[line ") {
+                expectations.errors.push(comment.to_string());
+            } else if comment.starts_with("Error") {
+                expectations
+                    .errors
+                    .push(format!("[line {}] {}", line, comment));
+            }
+        }
+
+        expectations
+    }
+
+    fn status(&self) -> i32 {
+        if !self.errors.is_empty() {
+            65
+        } else if self.runtime_error.is_some() {
+            70
+        } else {
+            0
+        }
+    }
+}
+
+fn lox_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(lox_files(&path));
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Runs `script` and describes how it differs from its annotations.
+fn check(script: &Path, args: &[&str]) -> Result<(), String> {
+    let source = std::fs::read_to_string(script).unwrap();
+    let expected = Expectations::parse(&source);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jlox-rs"))
+        .args(args)
+        .arg(script)
+        .output()
+        .expect("failed to run jlox");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let mut problems = vec![];
+    let output_lines: Vec<&str> = stdout.lines().collect();
+    if output_lines != expected.output {
+        problems.push(format!(
+            "expected output {:?}, got {:?}",
+            expected.output, output_lines
+        ));
+    }
+
+    let stderr = stderr.trim_end();
+    let expected_stderr = match expected.runtime_error {
+        Some(ref error) => error.clone(),
+        None => expected.errors.join("\n"),
+    };
+    if stderr != expected_stderr {
+        problems.push(format!(
+            "expected errors {:?}, got {:?}",
+            expected_stderr, stderr
+        ));
+    }
+
+    if output.status.code() != Some(expected.status()) {
+        problems.push(format!(
+            "expected exit status {}, got {:?}",
+            expected.status(),
+            output.status.code()
+        ));
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("\n  "))
+    }
+}
+
+fn check_all(args: &[&str]) {
+    let mut scripts = vec![];
+    for dir in DIRS {
+        scripts.extend(lox_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join(dir)));
+    }
+    assert!(scripts.len() > DIRS.len());
+
+    let failures: Vec<String> = scripts
+        .iter()
+        .filter_map(|script| {
+            check(script, args)
+                .err()
+                .map(|problem| format!("{}:\n  {}", script.display(), problem))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} scripts failed:\n{}",
+        failures.len(),
+        scripts.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn tree_walker_matches_expectations() {
+    check_all(&[]);
+}
+
+#[test]
+fn closure_mode_matches_expectations() {
+    check_all(&["--closure"]);
+}
+
+#[test]
+fn vm_matches_expectations() {
+    check_all(&["--vm"]);
+}
@@ -0,0 +1,191 @@
+use std::{
+    io::BufReader,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use jlox_rs::lsp::{read_message, write_message};
+use serde_json::{json, Value};
+
+/// Talks to `jlox-lsp` the way an editor would.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jlox-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to run jlox-lsp");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Self {
+            child,
+            stdin,
+            stdout,
+            next_id: 0,
+        }
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        write_message(&mut self.stdin, &message).unwrap();
+
+        let response = self.receive();
+        assert_eq!(response["id"], id);
+        response
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut self.stdin, &message).unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        read_message(&mut self.stdout)
+            .unwrap()
+            .expect("server hung up")
+    }
+
+    fn open(&mut self, uri: &str, text: &str) -> Value {
+        self.notify(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": uri, "languageId": "lox", "version": 1, "text": text } }),
+        );
+        let published = self.receive();
+        assert_eq!(published["method"], "textDocument/publishDiagnostics");
+        published["params"]["diagnostics"].clone()
+    }
+
+    fn exit(mut self) -> Option<i32> {
+        assert_eq!(self.request("shutdown", Value::Null)["result"], Value::Null);
+        self.notify("exit", Value::Null);
+        self.child.wait().unwrap().code()
+    }
+}
+
+fn at(uri: &str, line: u64, character: u64) -> Value {
+    json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+}
+
+fn range(line: u64, start: u64, end: u64) -> Value {
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end },
+    })
+}
+
+const PROGRAM: &str = "var total = 0;
+fun add(n) {
+  total = total + n;
+}
+add(2);
+";
+
+#[test]
+fn initializes_and_shuts_down() {
+    let mut client = Client::start();
+    let response = client.request("initialize", json!({ "capabilities": {} }));
+    let capabilities = &response["result"]["capabilities"];
+    assert_eq!(capabilities["textDocumentSync"], 1);
+    assert_eq!(capabilities["definitionProvider"], true);
+    client.notify("initialized", json!({}));
+
+    let response = client.request("workspace/symbol", json!({ "query": "" }));
+    assert_eq!(response["error"]["code"], -32601);
+
+    assert_eq!(client.exit(), Some(0));
+}
+
+#[test]
+fn publishes_diagnostics_on_change() {
+    let mut client = Client::start();
+    client.request("initialize", json!({ "capabilities": {} }));
+
+    let diagnostics = client.open("file:///a.lox", "var a = 1;\nprint a +;\n");
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"], range(1, 9, 10));
+    assert_eq!(diagnostics[0]["message"], "Expect expression.");
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": "file:///a.lox", "version": 2 },
+            "contentChanges": [{ "text": "var a = 1;\nprint a;\n" }],
+        }),
+    );
+    let published = client.receive();
+    assert_eq!(published["params"]["diagnostics"], json!([]));
+
+    assert_eq!(client.exit(), Some(0));
+}
+
+#[test]
+fn finds_definitions_and_references() {
+    let uri = "file:///add.lox";
+    let mut client = Client::start();
+    client.request("initialize", json!({ "capabilities": {} }));
+    assert_eq!(client.open(uri, PROGRAM), json!([]));
+
+    // `n` in `total + n` goes to the parameter.
+    let response = client.request("textDocument/definition", at(uri, 2, 18));
+    assert_eq!(
+        response["result"],
+        json!({ "uri": uri, "range": range(1, 8, 9) })
+    );
+
+    let mut params = at(uri, 0, 5);
+    params["context"] = json!({ "includeDeclaration": false });
+    let response = client.request("textDocument/references", params);
+    let lines: Vec<&Value> = response["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| &location["range"])
+        .collect();
+    assert_eq!(lines, vec![&range(2, 2, 7), &range(2, 10, 15)]);
+
+    let response = client.request("textDocument/hover", at(uri, 4, 1));
+    let hover = response["result"]["contents"]["value"].as_str().unwrap();
+    assert!(hover.starts_with("```lox\nfun add(n)\n```"), "{}", hover);
+
+    let response = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    let names: Vec<&Value> = response["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| &symbol["name"])
+        .collect();
+    assert_eq!(names, vec!["total", "add"]);
+
+    let response = client.request("textDocument/completion", at(uri, 5, 0));
+    let labels: Vec<&str> = response["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|item| item["label"].as_str())
+        .collect();
+    for label in ["while", "clock", "total", "add"] {
+        assert!(labels.contains(&label), "missing {}", label);
+    }
+
+    assert_eq!(client.exit(), Some(0));
+}
+
+#[test]
+fn exits_with_an_error_without_shutdown() {
+    let mut client = Client::start();
+    client.request("initialize", json!({ "capabilities": {} }));
+    client.notify("exit", Value::Null);
+    assert_eq!(client.child.wait().unwrap().code(), Some(1));
+}